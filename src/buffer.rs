@@ -0,0 +1,288 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Off-screen frame buffering.
+//!
+//! Frames are drawn into a [`FrameBuffer`] rather than directly to the terminal, and are then written by comparing
+//! them against the previously written frame. Only the cells that differ are written, which avoids the visible flash
+//! caused by clearing the terminal and greatly reduces the output produced by animations with static backgrounds.
+//!
+//! The cells of each drawn row may also be kept in a [`RowCache`], so that rows whose pixels are unchanged between
+//! frames are not converted into cells again.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use crossterm::cursor::{MoveRight, MoveTo};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+
+use crate::color::ColorTarget;
+
+/// A single drawn cell of the terminal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub character: char,
+    pub color: Option<Color>,
+    pub target: ColorTarget,
+}
+
+impl Cell {
+    /// A cell that is visually empty.
+    pub const BLANK: Self = Self::plain(' ');
+
+    /// Creates a cell that draws the given character without any color.
+    pub const fn plain(character: char) -> Self {
+        Self { character, color: None, target: ColorTarget::Foreground }
+    }
+
+    /// Queues the commands that switch the terminal from the colors of the previous cell to the colors of this cell.
+    pub fn queue_color(&self, writer: &mut impl Write, previous: Option<&Self>) -> std::io::Result<()> {
+        if previous.is_some_and(|previous| previous.color == self.color && previous.target == self.target) {
+            return Ok(());
+        }
+
+        // Setting one of the colors leaves the other in place, so a color set for the other target is reset first.
+        let reset = match previous {
+            Some(previous) => previous.color.is_some() && previous.target != self.target,
+            None => self.target == ColorTarget::Background,
+        };
+
+        if self.color.is_none() || reset {
+            crossterm::queue!(writer, ResetColor)?;
+        }
+
+        match (self.color, self.target) {
+            (Some(color), ColorTarget::Foreground) => crossterm::queue!(writer, SetForegroundColor(color)),
+            (Some(color), ColorTarget::Background) => crossterm::queue!(writer, SetBackgroundColor(color)),
+            (None, _) => Ok(()),
+        }
+    }
+}
+
+/// A grid of cells with the dimensions of the terminal, where cells that have not been drawn are left as [`None`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameBuffer {
+    width: u16,
+    height: u16,
+    cells: Box<[Option<Cell>]>,
+}
+
+impl FrameBuffer {
+    pub fn new((width, height): (u16, u16)) -> Self {
+        Self { width, height, cells: vec![None; width as usize * height as usize].into_boxed_slice() }
+    }
+
+    pub const fn size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    pub fn get(&self, x: u16, y: u16) -> Option<Cell> {
+        if x < self.width && y < self.height { self.cells[y as usize * self.width as usize + x as usize] } else { None }
+    }
+
+    /// Returns an iterator over the cells that have been drawn, in order from left to right and top to bottom.
+    pub fn drawn_cells(&self) -> impl Iterator<Item = Cell> + '_ {
+        self.cells.iter().filter_map(|cell| *cell)
+    }
+
+    /// Returns the number of rows up to and including the last row that contains a drawn cell.
+    pub fn drawn_height(&self) -> u16 {
+        let row_length = self.width.max(1) as usize;
+
+        self.cells
+            .chunks_exact(row_length)
+            .rposition(|row| row.iter().any(Option::is_some))
+            .map_or(0, |row| row as u16 + 1)
+    }
+
+    pub fn set(&mut self, x: u16, y: u16, cell: Cell) {
+        if x < self.width && y < self.height {
+            self.cells[y as usize * self.width as usize + x as usize] = Some(cell);
+        }
+    }
+
+    /// Returns a frame containing the given number of rows of this frame, starting at the given row.
+    ///
+    /// Rows beyond the end of this frame are left undrawn.
+    pub fn rows(&self, start: u16, height: u16) -> Self {
+        let mut frame = Self::new((self.width, height));
+        let row_length = self.width.max(1) as usize;
+        let source_rows = self.cells.chunks_exact(row_length).skip(start as usize);
+
+        for (target, source) in frame.cells.chunks_exact_mut(row_length).zip(source_rows) {
+            target.copy_from_slice(source);
+        }
+
+        frame
+    }
+
+    /// Sets every cell that has not been drawn to the given cell.
+    pub fn fill(&mut self, cell: Cell) {
+        self.cells.iter_mut().filter(|current| current.is_none()).for_each(|current| *current = Some(cell));
+    }
+
+    /// Draws the given lines of text within a bordered box, with its top left corner at the given position.
+    pub fn draw_text_box(&mut self, (x, y): (u16, u16), lines: &[Box<str>]) {
+        let (width, height) = Self::text_box_size(lines);
+
+        for row in 0 .. height {
+            for column in 0 .. width {
+                let (left, right) = (column == 0, column == width - 1);
+                let (top, bottom) = (row == 0, row == height - 1);
+
+                let character = match (left, right, top, bottom) {
+                    (true, _, true, _) => '┌',
+                    (_, true, true, _) => '┐',
+                    (true, _, _, true) => '└',
+                    (_, true, _, true) => '┘',
+                    (_, _, true, _) | (_, _, _, true) => '─',
+                    (true, ..) | (_, true, ..) => '│',
+                    _ => {
+                        let line = &lines[row as usize - 1];
+
+                        (column as usize).checked_sub(2).and_then(|index| line.chars().nth(index)).unwrap_or(' ')
+                    }
+                };
+
+                self.set(x.saturating_add(column), y.saturating_add(row), Cell::plain(character));
+            }
+        }
+    }
+
+    /// Returns the size of the box drawn around the given lines of text by [`draw_text_box`](Self::draw_text_box).
+    pub fn text_box_size(lines: &[Box<str>]) -> (u16, u16) {
+        let text_width = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+
+        // Each line is padded by a border and a space on either side.
+        ((text_width + 4).min(u16::MAX as usize) as u16, (lines.len() + 2).min(u16::MAX as usize) as u16)
+    }
+
+    /// Writes this frame to the terminal, skipping the cells that are unchanged since the previous frame.
+    ///
+    /// Without a previous frame of the same size, every drawn cell is written, and cells that were not drawn are only
+    /// written as blanks if `fill_blanks` is set.
+    pub fn queue(&self, writer: &mut impl Write, previous: Option<&Self>, fill_blanks: bool) -> std::io::Result<()> {
+        let previous = previous.filter(|previous| previous.size() == self.size());
+        let mut cursor = None;
+        let mut previous_cell = None;
+
+        for (index, cell) in self.cells.iter().enumerate() {
+            let cell = match (cell, previous.map(|previous| previous.cells[index])) {
+                (cell, Some(previous)) if *cell == previous => continue,
+                (Some(cell), _) => *cell,
+                (None, Some(_)) => Cell::BLANK,
+                (None, None) if fill_blanks => Cell::BLANK,
+                (None, None) => continue,
+            };
+
+            let position = ((index % self.width as usize) as u16, (index / self.width as usize) as u16);
+
+            // Skipping over unchanged cells within the same row is shorter than moving the cursor to an absolute
+            // position.
+            match cursor {
+                Some(cursor) if cursor == position => {}
+                Some((column, row)) if row == position.1 => crossterm::queue!(writer, MoveRight(position.0 - column))?,
+                _ => crossterm::queue!(writer, MoveTo(position.0, position.1))?,
+            }
+
+            cell.queue_color(writer, previous_cell.as_ref())?;
+
+            previous_cell = Some(cell);
+
+            crossterm::queue!(writer, Print(cell.character))?;
+
+            // The cursor wraps at the end of each row, so it is only known to be in place within the same row.
+            cursor = (position.0 + 1 < self.width).then_some((position.0 + 1, position.1));
+        }
+
+        // A background color would otherwise fill any text that is printed after the frame.
+        if previous_cell.is_some_and(|cell| cell.color.is_some() && cell.target == ColorTarget::Background) {
+            crossterm::queue!(writer, ResetColor)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes this frame as lines of text, which may be printed by programs that do not position the cursor.
+    ///
+    /// Cells that were not drawn are written as blanks, and each line is ended after its last drawn cell. Colors are
+    /// only written if `with_color` is set, and each line is written as a hyperlink to `link` if one is given.
+    pub fn write_lines(&self, writer: &mut impl Write, with_color: bool, link: Option<&str>) -> std::io::Result<()> {
+        for row in self.cells.chunks_exact(self.width.max(1) as usize) {
+            let length = row.iter().rposition(Option::is_some).map_or(0, |index| index + 1);
+            let mut previous_cell = Cell::BLANK;
+
+            // Hyperlinks are opened and closed on every line, since not every terminal continues them across lines.
+            if let Some(link) = link {
+                write!(writer, "\x1B]8;;{link}\x1B\\")?;
+            }
+
+            for cell in row[.. length].iter().map(|cell| cell.unwrap_or(Cell::BLANK)) {
+                if with_color {
+                    cell.queue_color(writer, Some(&previous_cell))?;
+
+                    previous_cell = cell;
+                }
+
+                crossterm::queue!(writer, Print(cell.character))?;
+            }
+
+            if previous_cell.color.is_some() {
+                crossterm::queue!(writer, ResetColor)?;
+            }
+            if link.is_some() {
+                write!(writer, "\x1B]8;;\x1B\\")?;
+            }
+
+            writeln!(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The cells of a single drawn row, where cells that were not drawn are left as [`None`].
+pub type Row = Box<[Option<Cell>]>;
+
+/// A bounded cache of drawn rows, keyed by a hash of the pixels and settings that they were drawn from.
+///
+/// When the cache is full, the row that was least recently used is evicted.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RowCache {
+    capacity: usize,
+    uses: u64,
+    rows: HashMap<u64, (Row, u64)>,
+}
+
+impl RowCache {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, uses: 0, rows: HashMap::with_capacity(capacity) }
+    }
+
+    /// Returns the row stored under the given key, drawing and storing it first if it is not cached.
+    pub fn get_or_insert_with(&mut self, key: u64, draw: impl FnOnce() -> Row) -> &[Option<Cell>] {
+        self.uses += 1;
+
+        if !self.rows.contains_key(&key)
+            && self.rows.len() >= self.capacity
+            && let Some(oldest) = self.rows.iter().min_by_key(|(_, (_, used))| *used).map(|(key, _)| *key)
+        {
+            self.rows.remove(&oldest);
+        }
+
+        let (row, used) = self.rows.entry(key).or_insert_with(|| (draw(), 0));
+
+        *used = self.uses;
+
+        row
+    }
+}
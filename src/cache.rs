@@ -0,0 +1,136 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Storage of glyph measurements.
+//!
+//! Measuring every glyph of a font is slow, so measurements are stored in a [`BrightnessCache`] and reused by later
+//! runs. Each set of measurements is stored under a key that identifies the font and the settings used to rasterize
+//! it, and the storage itself is left to the implementation, such as the [`FileCache`] used by default.
+//!
+//! The final brightnesses of a charset may also be written to a portable [`FontBundle`], which can be loaded on
+//! machines that do not have the font installed at all.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// The raw measurements of a single rasterized glyph, cached independently of any charset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GlyphMeasurement {
+    pub width: u32,
+    pub height: u32,
+    pub coverage: u64,
+    /// The coverage of the red, green and blue subpixels, if the glyph was rasterized for LCD displays.
+    ///
+    /// This is omitted from measurements of glyphs rasterized normally, which keeps their cached layout unchanged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subpixel_coverage: Option<[u64; 3]>,
+}
+
+/// A storage backend for glyph measurements.
+pub trait BrightnessCache {
+    /// Returns the measurements stored under the given key, or [`None`] if none are stored.
+    fn get(&self, key: &str) -> Result<Option<HashMap<char, GlyphMeasurement>>>;
+
+    /// Stores the given measurements under the given key, replacing any that were previously stored.
+    fn put(&mut self, key: &str, measurements: &HashMap<char, GlyphMeasurement>) -> Result<()>;
+}
+
+/// A cache that stores each set of measurements as a JSON file within a directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileCache {
+    directory: Box<Path>,
+}
+
+impl FileCache {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self { directory: directory.into().into_boxed_path() }
+    }
+
+    /// Returns the path of the file that stores the measurements under the given key.
+    pub fn path(&self, key: &str) -> PathBuf {
+        self.directory.join(format!("{key}.json"))
+    }
+}
+
+impl BrightnessCache for FileCache {
+    fn get(&self, key: &str) -> Result<Option<HashMap<char, GlyphMeasurement>>> {
+        let path = self.path(key);
+
+        if let Ok(file) = File::open(&path).map(BufReader::new)
+            && let Ok(measurements) = serde_json::from_reader(file)
+        {
+            return Ok(Some(measurements));
+        }
+
+        // A file that cannot be parsed is from an older version or was corrupted, and is replaced on the next write.
+        if path.try_exists()? {
+            std::fs::remove_file(&path)?;
+        }
+
+        Ok(None)
+    }
+
+    fn put(&mut self, key: &str, measurements: &HashMap<char, GlyphMeasurement>) -> Result<()> {
+        std::fs::create_dir_all(&self.directory)?;
+
+        serde_json::to_writer(BufWriter::new(File::create(self.path(key))?), measurements).map_err(Error::Cache)
+    }
+}
+
+/// The measured brightnesses of a charset, along with the identity of the font and settings they were measured with.
+///
+/// Bundles are conventionally saved with the `.term-render-font` extension.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FontBundle {
+    pub font_name: Box<str>,
+    pub glyph_size: Option<f32>,
+    pub glyph_scale: f32,
+    pub hinted: bool,
+    #[serde(default)]
+    pub subpixel: bool,
+    pub brightnesses: HashMap<char, u16>,
+}
+
+impl FontBundle {
+    pub fn read(path: &Path) -> Result<Self> {
+        serde_json::from_reader(BufReader::new(File::open(path)?)).map_err(Error::Bundle)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        serde_json::to_writer(BufWriter::new(File::create(path)?), self).map_err(Error::Bundle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FontBundle;
+    use crate::error::Error;
+
+    #[test]
+    fn malformed_bundles_are_bundle_errors() {
+        let path = std::env::temp_dir().join(format!("term-render-{}.term-render-font", std::process::id()));
+
+        std::fs::write(&path, "{").expect("the bundle should be written");
+
+        let result = FontBundle::read(&path);
+
+        std::fs::remove_file(&path).expect("the bundle should be removed");
+
+        assert!(matches!(result, Err(Error::Bundle(_))), "the bundle was read as {result:?}");
+    }
+}
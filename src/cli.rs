@@ -0,0 +1,531 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! The command-line arguments of the program.
+
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::{Args, Parser, Subcommand};
+use crossterm::event::KeyCode;
+use unicode_width::UnicodeWidthChar;
+
+use crate::color::{ColorBlindness, ColorDepth, ColorDistance, ColorTarget};
+use crate::commands::export::{ExportEncoding, ExportFormat};
+use crate::commands::view::RenderTarget;
+use crate::measure::{Hinting, Parallelism, Preset, Slant};
+use crate::render::{Anchor, BadgeStyle, ClearMode, SampleFilter, ScanlineStyle};
+
+/// A basic ASCII-based terminal renderer.
+#[derive(Debug, Parser)]
+pub struct Arguments {
+    /// Whether to clean up all caches before running.
+    #[arg(short, long, global = true)]
+    pub clean: bool,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// An action performed by the program.
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Draws an image in the terminal until a quit key is pressed.
+    View(ViewArguments),
+    /// Writes an image drawn as colored text to a file.
+    Export(ExportArguments),
+    /// Prints an image that exactly fills a file manager's preview pane, then exits.
+    Preview(PreviewArguments),
+    /// Lists the font families that may be given to `--font`.
+    ListFonts,
+    /// Measures and caches the brightness of each character, then prints the resolved font and its cache path.
+    Precompute(PrecomputeArguments),
+    /// Measures the time taken to draw an image.
+    Bench(BenchArguments),
+    /// Reports the size, color support, graphics protocols and background color detected for the terminal.
+    Probe,
+}
+
+/// The arguments used to measure the brightness of each character.
+#[derive(Debug, Args)]
+pub struct MeasureArguments {
+    /// Specifies the font used by the terminal during rendering for more accurate character brightnesses.
+    ///
+    /// May be given several times to list the fonts that the terminal falls back to, in which case each character is
+    /// measured in the first font that contains it. The font `embedded` is compiled into the binary rather than
+    /// installed, so its measurements are identical on every machine.
+    #[arg(short, long)]
+    pub font: Vec<Box<str>>,
+    /// The size in pixels at which glyphs are rasterized when measuring their brightness.
+    #[arg(long, value_parser = self::parse_positive)]
+    pub glyph_size: Option<f32>,
+    /// The factor by which the glyph size is multiplied, matching the scaling applied by HiDPI displays.
+    #[arg(long, default_value_t = 1.0, value_parser = self::parse_positive, requires = "glyph_size")]
+    pub glyph_scale: f32,
+    /// The slant of the font faces whose glyphs are measured, which should match the face used by the terminal.
+    #[arg(long, value_enum, default_value_t = Slant::default())]
+    pub slant: Slant,
+    /// The hinting applied to glyph outlines when measuring their brightness.
+    #[arg(long, value_enum, default_value_t = Hinting::default())]
+    pub hinting: Hinting,
+    /// Whether to measure glyphs rasterized with LCD subpixel anti-aliasing, as drawn by terminals that render text
+    /// for LCD displays, recording the coverage of each color channel.
+    #[arg(long)]
+    pub subpixel: bool,
+    /// The built-in set of characters used to draw the image.
+    #[arg(long, value_enum, default_value_t = Preset::default())]
+    pub preset: Preset,
+    /// Specifies the exact set of characters used to draw the image, overriding the preset.
+    #[arg(long)]
+    pub charset: Option<Box<str>>,
+    /// Whether to keep characters that are not exactly one column wide, which may break the alignment of columns.
+    #[arg(long)]
+    pub all_glyphs: bool,
+    /// The percentile of glyph brightnesses that is treated as fully bright, brightening the rest of the ramp when the
+    /// brightest glyphs are outliers.
+    #[arg(long, value_name = "PERCENTILE", default_value_t = 100.0, value_parser = self::parse_percentile)]
+    pub normalize_percentile: f32,
+    /// The number of threads used to measure glyphs, which defaults to one for each core.
+    #[arg(long, value_name = "N")]
+    pub threads: Option<NonZeroUsize>,
+    /// Whether to measure glyphs one at a time on the main thread, which keeps profiling and debugging deterministic.
+    #[arg(long, conflicts_with = "threads")]
+    pub single_thread: bool,
+}
+
+impl MeasureArguments {
+    pub const fn parallelism(&self) -> Parallelism {
+        if self.single_thread { Parallelism::Sequential } else { Parallelism::Parallel(self.threads) }
+    }
+}
+
+/// The arguments of the `precompute` command.
+#[derive(Debug, Args)]
+pub struct PrecomputeArguments {
+    #[command(flatten)]
+    pub measure: MeasureArguments,
+
+    /// Specifies a file to write the measured brightnesses to, which may be given to `--import-font-cache` on machines
+    /// without the font installed.
+    #[arg(long, value_name = "PATH")]
+    pub export_font_cache: Option<Box<Path>>,
+    /// Whether to only print the resolved fonts and their cache paths, without measuring any glyphs.
+    #[arg(long, alias = "show-font", conflicts_with = "export_font_cache")]
+    pub dry_run: bool,
+}
+
+/// The arguments used to draw an image.
+#[derive(Debug, Args)]
+pub struct RenderArguments {
+    #[command(flatten)]
+    pub measure: MeasureArguments,
+
+    /// Specifies a JSON file of brightness ranges and their characters, overriding the font's measurements.
+    #[arg(long)]
+    pub mono_threshold_map: Option<Box<Path>>,
+    /// Specifies a file written by `precompute --export-font-cache` to load brightnesses from, overriding the font's
+    /// measurements.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["mono_threshold_map", "font", "charset"])]
+    pub import_font_cache: Option<Box<Path>>,
+    /// Whether to only draw with a subset of characters whose brightnesses are evenly spaced, which avoids the
+    /// jarring changes between characters of similar brightness.
+    #[arg(long, conflicts_with = "mono_threshold_map")]
+    pub smooth: bool,
+    /// Specifies that brightnesses are rounded to N evenly spaced levels before choosing characters, producing a
+    /// banded look that uses at most N characters.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(2 ..))]
+    pub levels: Option<u16>,
+    /// Specifies that the brightness of each cell is the average of an N by N grid of samples, reducing aliasing at
+    /// the cost of sampling the image more often.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(2 ..= 16))]
+    pub supersample: Option<u32>,
+    /// Whether to resize the image in linear light rather than in sRGB, which keeps thin bright details from being
+    /// darkened when the image is scaled down.
+    #[arg(long, conflicts_with = "supersample")]
+    pub downsample_gamma_correct: bool,
+    /// The radius of the Gaussian blur applied to the image before it is drawn, for a soft focus or to reduce the
+    /// noise of grainy photos.
+    #[arg(long, value_name = "RADIUS", default_value_t = 0.0, value_parser = self::parse_factor)]
+    pub blur: f32,
+    /// The alpha value at or below which pixels are treated as fully transparent, removing anti-aliased halos.
+    #[arg(long, value_name = "ALPHA", default_value_t = 0)]
+    pub antialias_threshold: u8,
+    /// Whether to crop the image to the bounds of its visible pixels, so that padding around sprites is not drawn.
+    #[arg(long)]
+    pub trim: bool,
+    /// The page of a multi-page image, such as a TIFF file or an animated GIF, to draw.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1 ..))]
+    pub page: u32,
+    /// Specifies a second image, drawing the per-pixel difference between it and the image instead.
+    #[arg(long, value_name = "OTHER")]
+    pub diff: Option<Box<Path>>,
+    /// Whether to resize the second image to the size of the first when their sizes differ.
+    #[arg(long, requires = "diff")]
+    pub diff_resize: bool,
+    /// Whether to draw the difference as a heat map, from black through red and yellow to white.
+    #[arg(long, requires = "diff")]
+    pub diff_heat_map: bool,
+    /// The character drawn when no other character can represent a pixel's brightness.
+    #[arg(long, default_value_t = ' ', value_parser = self::parse_narrow_char)]
+    pub fallback_char: char,
+
+    /// Whether to draw the image without color.
+    #[arg(short, long)]
+    pub plain: bool,
+    /// The factor by which to scale the area that the image is drawn within.
+    #[arg(long, default_value_t = 1.0, value_parser = self::parse_positive)]
+    pub scale: f32,
+    /// Whether to stretch the image to fill the terminal, ignoring its aspect ratio.
+    #[arg(long)]
+    pub stretch_to_fill: bool,
+    /// Whether to downscale the image before stretching it, which is faster for large images but softens fine detail.
+    #[arg(long)]
+    pub downscale_first: bool,
+    /// Whether to resize the image with a fast box filter in a single pass, trading quality for speed when drawing
+    /// many images.
+    #[arg(long, conflicts_with_all = ["downscale_first", "supersample", "downsample_gamma_correct"])]
+    pub thumbnail: bool,
+    /// Whether to draw the image as a grid of full and empty blocks, scaling each module of a QR code or barcode to a
+    /// whole number of cells so that it remains scannable.
+    #[arg(long, conflicts_with_all = ["stretch_to_fill", "compare_colors"])]
+    pub block_grid: bool,
+    /// The brightness below which pixels are treated as dark modules when drawing a block grid.
+    #[arg(long, value_name = "BRIGHTNESS", default_value_t = 128, requires = "block_grid")]
+    pub block_threshold: u8,
+    /// The sampling used when resizing the image to choose each character.
+    #[arg(long, value_enum, default_value_t = SampleFilter::default())]
+    pub luma_filter: SampleFilter,
+    /// The sampling used when resizing the image to choose each character's color.
+    #[arg(long, value_enum, default_value_t = SampleFilter::default(), conflicts_with = "plain")]
+    pub color_filter: SampleFilter,
+    /// Specifies that each run of N cells along a row is drawn in the color of its first cell, while characters are
+    /// still chosen for every cell, producing blocks of color.
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1 ..))]
+    pub color_interval: u16,
+    /// The point of the terminal at which the image is placed when it does not fill it.
+    #[arg(long, value_enum, default_value_t = Anchor::default(), conflicts_with = "stretch_to_fill")]
+    pub anchor: Anchor,
+    /// Draws a dimmed copy of the image behind it, offset by the given number of columns and rows, as a drop shadow.
+    #[arg(long, value_name = "COLUMNS,ROWS", value_parser = self::parse_offset)]
+    pub shadow: Option<(u16, u16)>,
+    /// The opacity of the drop shadow, between 0 and 1.
+    #[arg(long, default_value_t = 0.5, value_parser = self::parse_opacity, requires = "shadow")]
+    pub shadow_opacity: f32,
+    /// Whether to fill the cells that the image does not cover with spaces, so that the output is always a full
+    /// rectangle.
+    #[arg(long)]
+    pub pad: bool,
+    /// Specifies that only one in every N rows is fully drawn, producing a scanline effect.
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1 ..))]
+    pub line_spacing: u16,
+    /// How the rows between scanlines are drawn when a line spacing is given.
+    #[arg(long, value_enum, default_value_t = ScanlineStyle::default())]
+    pub scanline_style: ScanlineStyle,
+    /// The number of colors used when drawing the image.
+    #[arg(long, value_enum, default_value_t = ColorDepth::default())]
+    pub color_depth: ColorDepth,
+    /// Whether to draw grayscale images in color, which otherwise only repeats the brightness of each character.
+    #[arg(long, conflicts_with = "plain")]
+    pub color_grayscale: bool,
+    /// The part of each cell that is drawn in the image's color.
+    #[arg(long, value_enum, default_value_t = ColorTarget::default(), conflicts_with = "plain")]
+    pub color_target: ColorTarget,
+    /// The metric used to find the nearest palette color when drawing with fewer colors.
+    #[arg(long, value_enum, default_value_t = ColorDistance::default(), conflicts_with = "plain")]
+    pub color_distance: ColorDistance,
+    /// The terminal's background color as a hex code, used to blend transparent pixels and to choose characters.
+    ///
+    /// Defaults to the color reported by the terminal, so that images are drawn correctly on light backgrounds, or to
+    /// black if the terminal does not report one. Exports, previews and benchmarks never ask the terminal, so that
+    /// their output is the same wherever they are run, and default to black.
+    #[arg(long, value_name = "COLOR", value_parser = self::parse_color)]
+    pub terminal_bg: Option<[u8; 3]>,
+    /// Whether to choose characters by the brightness of each pixel even on light backgrounds, rather than inverting
+    /// the ramp so that brighter pixels are drawn with less ink.
+    #[arg(long)]
+    pub no_auto_invert: bool,
+    /// Whether to invert the displayed colors, like a photographic negative.
+    #[arg(long, conflicts_with = "plain")]
+    pub invert_colors: bool,
+    /// The factor by which to scale the saturation of displayed colors.
+    #[arg(long, default_value_t = 1.0, value_parser = self::parse_factor, conflicts_with = "plain")]
+    pub saturation: f32,
+    /// The number of degrees by which to rotate the hue of displayed colors.
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true, conflicts_with = "plain")]
+    pub hue_shift: f32,
+    /// Simulates how the displayed colors appear with the given form of color blindness.
+    #[arg(long, value_enum, conflicts_with = "plain")]
+    pub simulate: Option<ColorBlindness>,
+    /// Specifies the number of colors that the image is reduced to before drawing, for a posterized look.
+    #[arg(long, value_name = "COUNT", value_parser = clap::value_parser!(u16).range(1 ..), conflicts_with = "plain")]
+    pub max_colors: Option<u16>,
+    /// Whether to draw the image once for each color depth, side by side, for comparison.
+    #[arg(long, conflicts_with = "plain")]
+    pub compare_colors: bool,
+    /// Whether to draw the characters used to draw the image in a column beside it, from darkest to brightest, along
+    /// with their brightnesses.
+    #[arg(long)]
+    pub legend: bool,
+}
+
+/// The arguments used to specify the size of an image that is not drawn to the terminal.
+#[derive(Debug, Args)]
+pub struct SizeArguments {
+    /// The width of the drawn image in cells, defaulting to the width of the terminal.
+    #[arg(long)]
+    pub width: Option<u16>,
+    /// The height of the drawn image in cells, defaulting to the height of the terminal.
+    #[arg(long)]
+    pub height: Option<u16>,
+}
+
+/// The arguments of the `view` command.
+#[derive(Debug, Args)]
+pub struct ViewArguments {
+    /// The path to an image, or to a directory of numbered images played as an animation.
+    #[arg(required_unless_present_any = ["test_pattern", "clipboard", "stdin_commands", "show_font"])]
+    pub path: Option<Box<Path>>,
+    /// Whether to draw a generated test pattern instead of an image, for calibrating how images are drawn.
+    #[arg(long, conflicts_with_all = ["path", "clipboard"])]
+    pub test_pattern: bool,
+    /// Whether to draw the image held by the clipboard instead of an image file.
+    ///
+    /// The clipboard is read by running `wl-paste` on Wayland or `xclip` on X11, one of which must be installed.
+    #[arg(long, conflicts_with = "path")]
+    pub clipboard: bool,
+
+    #[command(flatten)]
+    pub render: RenderArguments,
+
+    /// How the terminal is cleared before drawing, defaulting to 'all', or to 'none' for badges.
+    #[arg(long, value_enum)]
+    pub clear: Option<ClearMode>,
+    /// Draws the image inline as a single-row badge at the cursor, then exits.
+    #[arg(long, value_enum, conflicts_with_all = ["compare_colors", "block_grid"])]
+    pub badge: Option<BadgeStyle>,
+    /// Whether to dither the colors of a half-block badge, which keeps gradients smooth when fewer colors are used.
+    #[arg(long, requires = "badge")]
+    pub half_block_dither: bool,
+    /// Whether to print the number of columns and rows the image would be drawn within, then exit.
+    #[arg(long, conflicts_with = "badge")]
+    pub dry_size: bool,
+    /// Whether to draw the image at the full width of the terminal, scrolling through any rows that do not fit.
+    #[arg(long, conflicts_with_all = ["badge", "stretch_to_fill", "compare_colors", "block_grid"])]
+    pub scroll: bool,
+    /// Whether to print the image once and exit without entering raw mode, for terminals where raw mode misbehaves.
+    #[arg(long, conflicts_with_all = ["badge", "dry_size", "scroll"])]
+    pub no_raw: bool,
+    /// Whether to keep running and draw each image path read from standard input, until 'q' or the end of input.
+    #[arg(long, alias = "server", conflicts_with_all = ["path", "test_pattern", "clipboard", "badge", "dry_size", "scroll"])]
+    pub stdin_commands: bool,
+    /// Whether to print the resolved fonts and their cache paths, then exit, as `precompute --dry-run` does.
+    #[arg(long, hide = true)]
+    pub show_font: bool,
+    /// Whether to leave the cursor on the row below the drawn image when exiting, so that the shell's prompt is
+    /// printed below it rather than over it.
+    #[arg(long, conflicts_with_all = ["badge", "dry_size"])]
+    pub cursor_below: bool,
+    /// The stream that the image is drawn to, which leaves the other free for programs that capture it.
+    #[arg(long, value_enum, default_value_t = RenderTarget::default())]
+    pub render_to: RenderTarget,
+
+    /// The number of frames drawn per second when playing an animation.
+    #[arg(long, default_value_t = 10.0, value_parser = self::parse_positive)]
+    pub fps: f32,
+    /// The maximum number of times the screen is redrawn per second, with input handled between redraws.
+    #[arg(long, value_parser = self::parse_positive)]
+    pub max_fps: Option<f32>,
+    /// Whether to draw the measured and target frame rates over animations.
+    #[arg(long)]
+    pub fps_counter: bool,
+    /// The number of milliseconds that drawing may take before the quality of later drawings is reduced.
+    #[arg(long, value_name = "MILLISECONDS", value_parser = self::parse_milliseconds)]
+    pub timing_budget: Option<Duration>,
+    /// Whether to report additional details about how images were drawn.
+    #[arg(short, long)]
+    pub verbose: bool,
+    /// The keys that exit the program; Ctrl-C always exits regardless.
+    #[arg(long = "quit-key", value_name = "KEY", value_parser = self::parse_key_code, default_values = ["q", "esc"])]
+    pub quit_keys: Vec<KeyCode>,
+    /// The number of seconds after which to automatically exit.
+    #[arg(short, long, value_parser = self::parse_seconds)]
+    pub timeout: Option<Duration>,
+}
+
+/// The arguments of the `export` command.
+#[derive(Debug, Args)]
+pub struct ExportArguments {
+    /// The path to an image.
+    #[arg(required_unless_present_any = ["test_pattern", "clipboard"])]
+    pub path: Option<Box<Path>>,
+    /// Whether to draw a generated test pattern instead of an image, for calibrating how images are drawn.
+    #[arg(long, conflicts_with_all = ["path", "clipboard"])]
+    pub test_pattern: bool,
+    /// Whether to draw the image held by the clipboard instead of an image file.
+    ///
+    /// The clipboard is read by running `wl-paste` on Wayland or `xclip` on X11, one of which must be installed.
+    #[arg(long, conflicts_with = "path")]
+    pub clipboard: bool,
+    /// The path of the written file, defaulting to the standard output.
+    #[arg(short, long)]
+    pub output: Option<Box<Path>>,
+    /// The format of the written file.
+    #[arg(long, value_enum, default_value_t = ExportFormat::default())]
+    pub format: ExportFormat,
+    /// The character encoding of the written file.
+    #[arg(long, value_enum, default_value_t = ExportEncoding::default())]
+    pub encoding: ExportEncoding,
+    /// Whether to pack characters by the advance widths of the font rather than placing them in a grid, which is
+    /// experimental and only supported by SVG exports.
+    #[arg(long, conflicts_with_all = ["mono_threshold_map", "import_font_cache"])]
+    pub proportional: bool,
+    /// The URL that the image was retrieved from, which the exported image is linked to when written as ANSI art.
+    #[arg(long, value_name = "URL")]
+    pub source_url: Option<Box<str>>,
+    /// Whether to write the settings that the image was drawn with to a JSON file beside the written file, named by
+    /// appending '.json' to its path.
+    #[arg(long, requires = "output")]
+    pub write_metadata: bool,
+    /// Whether to print how often each character was used and how the brightnesses of the drawn characters are
+    /// distributed, for judging how well a charset covers the image.
+    #[arg(long, conflicts_with = "legend")]
+    pub stats: bool,
+
+    #[command(flatten)]
+    pub size: SizeArguments,
+    #[command(flatten)]
+    pub render: RenderArguments,
+}
+
+/// The arguments of the `preview` command.
+#[derive(Debug, Args)]
+pub struct PreviewArguments {
+    /// The path to an image.
+    pub path: Box<Path>,
+    /// The width of the preview pane in cells.
+    #[arg(long, env = "FZF_PREVIEW_COLUMNS")]
+    pub width: u16,
+    /// The height of the preview pane in cells.
+    #[arg(long, env = "FZF_PREVIEW_LINES")]
+    pub height: u16,
+
+    #[command(flatten)]
+    pub render: RenderArguments,
+}
+
+/// The arguments of the `bench` command.
+#[derive(Debug, Args)]
+pub struct BenchArguments {
+    /// The path to an image.
+    #[arg(required_unless_present_any = ["test_pattern", "clipboard"])]
+    pub path: Option<Box<Path>>,
+    /// Whether to draw a generated test pattern instead of an image, for calibrating how images are drawn.
+    #[arg(long, conflicts_with_all = ["path", "clipboard"])]
+    pub test_pattern: bool,
+    /// Whether to draw the image held by the clipboard instead of an image file.
+    ///
+    /// The clipboard is read by running `wl-paste` on Wayland or `xclip` on X11, one of which must be installed.
+    #[arg(long, conflicts_with = "path")]
+    pub clipboard: bool,
+    /// The number of times to draw the image.
+    #[arg(short = 'n', long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1 ..))]
+    pub iterations: u32,
+
+    #[command(flatten)]
+    pub size: SizeArguments,
+    #[command(flatten)]
+    pub render: RenderArguments,
+}
+
+fn parse_milliseconds(value: &str) -> Result<Duration> {
+    Ok(Duration::from_millis(value.parse()?))
+}
+
+fn parse_seconds(value: &str) -> Result<Duration> {
+    Duration::try_from_secs_f64(value.parse()?).map_err(Into::into)
+}
+
+fn parse_key_code(value: &str) -> Result<KeyCode> {
+    let mut characters = value.chars();
+
+    if let (Some(character), None) = (characters.next(), characters.next()) {
+        return Ok(KeyCode::Char(character));
+    }
+
+    Ok(match &*value.to_ascii_lowercase() {
+        "enter" | "return" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "space" => KeyCode::Char(' '),
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        _ => anyhow::bail!("expected a single character or one of enter, esc, space, tab, backspace, or delete"),
+    })
+}
+
+fn parse_factor(value: &str) -> Result<f32> {
+    let factor: f32 = value.parse()?;
+
+    anyhow::ensure!(factor.is_finite() && factor >= 0.0, "expected a non-negative number");
+
+    Ok(factor)
+}
+
+fn parse_color(value: &str) -> Result<[u8; 3]> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+
+    anyhow::ensure!(hex.len() == 6 && hex.is_ascii(), "expected a hex color such as '#1E1E2E'");
+
+    let channel = |index: usize| u8::from_str_radix(&hex[index * 2 .. index * 2 + 2], 16);
+
+    Ok([channel(0)?, channel(1)?, channel(2)?])
+}
+
+fn parse_positive(value: &str) -> Result<f32> {
+    let number: f32 = value.parse()?;
+
+    anyhow::ensure!(number.is_finite() && number > 0.0, "expected a positive number");
+
+    Ok(number)
+}
+
+fn parse_percentile(value: &str) -> Result<f32> {
+    let percentile: f32 = value.parse()?;
+
+    anyhow::ensure!(percentile > 0.0 && percentile <= 100.0, "expected a percentile greater than 0 and at most 100");
+
+    Ok(percentile)
+}
+
+fn parse_offset(value: &str) -> Result<(u16, u16)> {
+    let (columns, rows) = value.split_once(',').ok_or_else(|| anyhow::anyhow!("expected an offset such as '1,1'"))?;
+
+    Ok((columns.trim().parse()?, rows.trim().parse()?))
+}
+
+fn parse_opacity(value: &str) -> Result<f32> {
+    let opacity: f32 = value.parse()?;
+
+    anyhow::ensure!((0.0 ..= 1.0).contains(&opacity), "expected a number between 0 and 1");
+
+    Ok(opacity)
+}
+
+fn parse_narrow_char(value: &str) -> Result<char> {
+    let character: char = value.parse()?;
+
+    anyhow::ensure!(character.width() == Some(1), "expected a character that is one column wide");
+
+    Ok(character)
+}
@@ -0,0 +1,72 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Reading images from the system clipboard.
+//!
+//! The clipboard is read through `wl-paste` on Wayland and `xclip` on X11, which avoids connecting to the display
+//! server directly, so other platforms are not supported. The types offered by the clipboard are listed first, so that
+//! an image is only requested if one was actually copied.
+
+use std::io::ErrorKind;
+use std::process::Command;
+
+use image::DynamicImage;
+
+use crate::error::{Error, Result};
+
+/// Decodes the image currently held by the clipboard.
+pub fn open() -> Result<DynamicImage> {
+    if cfg!(any(target_os = "macos", target_os = "windows")) {
+        return Err(Error::clipboard("reading the clipboard is only supported on Wayland and X11"));
+    }
+
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+    let types = if wayland {
+        self::run("wl-paste", &["--list-types"])?
+    } else {
+        self::run("xclip", &["-selection", "clipboard", "-target", "TARGETS", "-out"])?
+    };
+    let types = String::from_utf8_lossy(&types);
+    let mut image_types = types.lines().map(str::trim).filter(|mime_type| mime_type.starts_with("image/"));
+
+    // PNG is offered by nearly every program that copies images, and is decoded losslessly.
+    let mime_type = match image_types.clone().find(|mime_type| *mime_type == "image/png") {
+        Some(mime_type) => mime_type,
+        None => image_types.next().ok_or_else(|| Error::clipboard("the clipboard does not contain an image"))?,
+    };
+
+    let data = if wayland {
+        self::run("wl-paste", &["--no-newline", "--type", mime_type])?
+    } else {
+        self::run("xclip", &["-selection", "clipboard", "-target", mime_type, "-out"])?
+    };
+
+    image::load_from_memory(&data).map_err(Into::into)
+}
+
+fn run(program: &str, arguments: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new(program).args(arguments).output().map_err(|error| match error.kind() {
+        ErrorKind::NotFound => Error::clipboard(format!(
+            "reading the clipboard requires '{program}' to be installed, but it was not found"
+        )),
+        _ => error.into(),
+    })?;
+
+    // Both programs also fail when nothing has been copied, which they explain in their error output.
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        return Err(Error::clipboard(format!("'{program}' failed with {}: {}", output.status, stderr.trim())));
+    }
+
+    Ok(output.stdout)
+}
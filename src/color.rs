@@ -0,0 +1,519 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Color transformation and quantization.
+
+use std::sync::LazyLock;
+
+use clap::ValueEnum;
+use crossterm::style::Color;
+use image::{Rgba, RgbaImage};
+use serde::Serialize;
+
+const PROTANOPIA_MATRIX: [[f32; 3]; 3] =
+    [[0.152_286, 1.052_583, -0.204_868], [0.114_503, 0.786_281, 0.099_216], [-0.003_882, -0.048_116, 1.051_998]];
+const DEUTERANOPIA_MATRIX: [[f32; 3]; 3] =
+    [[0.367_322, 0.860_646, -0.227_968], [0.280_085, 0.672_501, 0.047_413], [-0.011_820, 0.042_940, 0.968_881]];
+const TRITANOPIA_MATRIX: [[f32; 3]; 3] =
+    [[1.255_528, -0.076_749, -0.178_779], [-0.078_411, 0.930_809, 0.147_602], [0.004_733, 0.691_367, 0.303_900]];
+
+static ANSI_PALETTE: LazyLock<[[u8; 3]; 256]> = LazyLock::new(|| {
+    const SYSTEM_COLORS: [[u8; 3]; 16] = [
+        [0x00, 0x00, 0x00],
+        [0xCD, 0x00, 0x00],
+        [0x00, 0xCD, 0x00],
+        [0xCD, 0xCD, 0x00],
+        [0x00, 0x00, 0xEE],
+        [0xCD, 0x00, 0xCD],
+        [0x00, 0xCD, 0xCD],
+        [0xE5, 0xE5, 0xE5],
+        [0x7F, 0x7F, 0x7F],
+        [0xFF, 0x00, 0x00],
+        [0x00, 0xFF, 0x00],
+        [0xFF, 0xFF, 0x00],
+        [0x5C, 0x5C, 0xFF],
+        [0xFF, 0x00, 0xFF],
+        [0x00, 0xFF, 0xFF],
+        [0xFF, 0xFF, 0xFF],
+    ];
+    const CUBE_LEVELS: [u8; 6] = [0x00, 0x5F, 0x87, 0xAF, 0xD7, 0xFF];
+
+    std::array::from_fn(|index| match index {
+        0 .. 16 => SYSTEM_COLORS[index],
+        16 .. 232 => {
+            let index = index - 16;
+
+            [CUBE_LEVELS[index / 36], CUBE_LEVELS[(index / 6) % 6], CUBE_LEVELS[index % 6]]
+        }
+        _ => [(8 + (index - 232) * 10) as u8; 3],
+    })
+});
+
+static ANSI_PALETTE_LAB: LazyLock<[[f32; 3]; 256]> = LazyLock::new(|| ANSI_PALETTE.map(self::rgb_to_lab));
+
+/// Returns the color displayed for the given index of the 256-color ANSI palette.
+pub fn ansi_rgb(index: u8) -> [u8; 3] {
+    ANSI_PALETTE[index as usize]
+}
+
+/// The part of a cell that is drawn in the cell's color.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, ValueEnum, Serialize)]
+pub enum ColorTarget {
+    /// The glyph is drawn in the color over the terminal's background.
+    #[default]
+    #[value(name = "fg")]
+    #[serde(rename = "fg")]
+    Foreground,
+    /// The cell's background is filled with the color, and the glyph is drawn over it in the terminal's foreground.
+    #[value(name = "bg")]
+    #[serde(rename = "bg")]
+    Background,
+}
+
+/// The number of colors that may be displayed by the terminal.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, ValueEnum, Serialize)]
+pub enum ColorDepth {
+    /// 24-bit RGB colors.
+    #[default]
+    #[value(name = "true")]
+    #[serde(rename = "true")]
+    TrueColor,
+    /// The 256-color ANSI palette.
+    #[value(name = "256")]
+    #[serde(rename = "256")]
+    Ansi256,
+    /// The 16-color ANSI palette.
+    #[value(name = "16")]
+    #[serde(rename = "16")]
+    Ansi16,
+}
+
+impl ColorDepth {
+    pub fn color(self, [r, g, b]: [u8; 3], distance: ColorDistance) -> Color {
+        let palette_range = match self {
+            Self::TrueColor => return Color::Rgb { r, g, b },
+            Self::Ansi256 => 16 .. 256,
+            Self::Ansi16 => 0 .. 16,
+        };
+
+        let index = match distance {
+            ColorDistance::Rgb => palette_range.min_by_key(|index| {
+                let [palette_r, palette_g, palette_b] = ANSI_PALETTE[*index];
+
+                (r.abs_diff(palette_r) as u32).pow(2)
+                    + (g.abs_diff(palette_g) as u32).pow(2)
+                    + (b.abs_diff(palette_b) as u32).pow(2)
+            }),
+            ColorDistance::Cie76 | ColorDistance::Cie94 => {
+                let lab = self::rgb_to_lab([r, g, b]);
+
+                palette_range.min_by(|lhs, rhs| {
+                    let lhs = distance.lab_distance(lab, ANSI_PALETTE_LAB[*lhs]);
+                    let rhs = distance.lab_distance(lab, ANSI_PALETTE_LAB[*rhs]);
+
+                    lhs.total_cmp(&rhs)
+                })
+            }
+        };
+
+        Color::AnsiValue(index.unwrap_or_default() as u8)
+    }
+}
+
+/// The metric used to find the nearest palette color when quantizing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorDistance {
+    /// Euclidean distance between RGB values, which is fast but not perceptually uniform.
+    #[default]
+    Rgb,
+    /// Euclidean distance between CIELAB values.
+    Cie76,
+    /// The CIE 1994 color difference, which weights chroma and hue differences by the color's chroma.
+    Cie94,
+}
+
+impl ColorDistance {
+    fn lab_distance(self, [l1, a1, b1]: [f32; 3], [l2, a2, b2]: [f32; 3]) -> f32 {
+        let (delta_l, delta_a, delta_b) = (l1 - l2, a1 - a2, b1 - b2);
+
+        match self {
+            Self::Rgb | Self::Cie76 => delta_l.powi(2) + delta_a.powi(2) + delta_b.powi(2),
+            Self::Cie94 => {
+                let chroma = a1.hypot(b1);
+                let delta_c = chroma - a2.hypot(b2);
+                let delta_h_squared = (delta_a.powi(2) + delta_b.powi(2) - delta_c.powi(2)).max(0.0);
+
+                let chroma_scale = 1.0 + 0.045 * chroma;
+                let hue_scale = 1.0 + 0.015 * chroma;
+
+                delta_l.powi(2) + (delta_c / chroma_scale).powi(2) + delta_h_squared / hue_scale.powi(2)
+            }
+        }
+    }
+}
+
+/// A form of color blindness that may be simulated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ColorBlindness {
+    /// The absence of long-wavelength cones, confusing reds and greens.
+    Protanopia,
+    /// The absence of medium-wavelength cones, confusing reds and greens.
+    Deuteranopia,
+    /// The absence of short-wavelength cones, confusing blues and yellows.
+    Tritanopia,
+}
+
+impl ColorBlindness {
+    /// Returns the matrix that maps linear RGB colors to those perceived with this form of color blindness.
+    ///
+    /// The matrices are those of Machado, Oliveira, and Fernandes (2009), which are derived by shifting the
+    /// sensitivity of the affected cones within the LMS color space.
+    const fn matrix(self) -> [[f32; 3]; 3] {
+        match self {
+            Self::Protanopia => PROTANOPIA_MATRIX,
+            Self::Deuteranopia => DEUTERANOPIA_MATRIX,
+            Self::Tritanopia => TRITANOPIA_MATRIX,
+        }
+    }
+
+    fn simulate(self, rgb: [u8; 3]) -> [u8; 3] {
+        let [r, g, b] = rgb.map(self::srgb_to_linear);
+
+        self.matrix().map(|row| self::linear_to_srgb(row[0] * r + row[1] * g + row[2] * b))
+    }
+}
+
+/// A single operation applied to the color of each drawn character.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorTransform {
+    /// Inverts each channel, like a photographic negative.
+    Invert,
+    /// Scales the saturation by the given factor.
+    Saturate(f32),
+    /// Rotates the hue by the given number of degrees.
+    RotateHue(f32),
+    /// Simulates how the color appears with the given form of color blindness.
+    Simulate(ColorBlindness),
+}
+
+impl ColorTransform {
+    pub fn is_identity(self) -> bool {
+        match self {
+            Self::Invert => false,
+            Self::Saturate(factor) => factor == 1.0,
+            Self::RotateHue(degrees) => degrees.rem_euclid(360.0) == 0.0,
+            Self::Simulate(_) => false,
+        }
+    }
+
+    pub fn apply(self, rgb: [u8; 3]) -> [u8; 3] {
+        if self.is_identity() {
+            return rgb;
+        }
+
+        match self {
+            Self::Invert => rgb.map(|channel| u8::MAX - channel),
+            Self::Saturate(factor) => {
+                let [hue, saturation, value] = self::rgb_to_hsv(rgb);
+
+                self::hsv_to_rgb([hue, (saturation * factor).min(1.0), value])
+            }
+            Self::RotateHue(degrees) => {
+                let [hue, saturation, value] = self::rgb_to_hsv(rgb);
+
+                self::hsv_to_rgb([hue + degrees, saturation, value])
+            }
+            Self::Simulate(color_blindness) => color_blindness.simulate(rgb),
+        }
+    }
+}
+
+/// An ordered list of color transforms followed by quantization to a color depth.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ColorPipeline {
+    pub depth: ColorDepth,
+    pub distance: ColorDistance,
+    background: [u8; 3],
+    palette: Option<Box<[[u8; 3]]>>,
+    transforms: Vec<ColorTransform>,
+}
+
+impl ColorPipeline {
+    pub const fn new(depth: ColorDepth, distance: ColorDistance, background: [u8; 3]) -> Self {
+        Self { depth, distance, background, palette: None, transforms: Vec::new() }
+    }
+
+    /// Sets the palette that each color is reduced to before it is transformed.
+    pub fn set_palette(&mut self, palette: Box<[[u8; 3]]>) {
+        self.palette = Some(palette);
+    }
+
+    pub fn palette(&self) -> Option<&[[u8; 3]]> {
+        self.palette.as_deref()
+    }
+
+    pub fn push(&mut self, transform: ColorTransform) {
+        if !transform.is_identity() {
+            self.transforms.push(transform);
+        }
+    }
+
+    pub fn transform(&self, rgb: [u8; 3]) -> [u8; 3] {
+        self.transforms.iter().fold(rgb, |rgb, transform| transform.apply(rgb))
+    }
+
+    pub fn color(&self, rgb: [u8; 3], alpha: u8) -> Color {
+        let rgb = match self.palette.as_deref() {
+            Some(palette) => palette.iter().copied().min_by_key(|color| self::rgb_distance(rgb, *color)).unwrap_or(rgb),
+            None => rgb,
+        };
+        let rgb = self.transform(rgb);
+        // Blend the color with the background by its transparency so that it remains consistent with the glyph's
+        // brightness.
+        let rgb = std::array::from_fn(|index| {
+            let blended = rgb[index] as u16 * alpha as u16 + self.background[index] as u16 * (u8::MAX - alpha) as u16;
+
+            (blended / u8::MAX as u16) as u8
+        });
+
+        self.depth.color(rgb, self.distance)
+    }
+
+    /// Returns the color of each pixel of the given image in order, using Floyd-Steinberg error diffusion to spread
+    /// the error of quantizing each pixel over its unvisited neighbors.
+    ///
+    /// The error is only diffused from opaque pixels with no transforms applied, since the displayed color of any
+    /// other pixel cannot be compared with the color that it was drawn from.
+    pub fn dither(&self, image: &RgbaImage) -> Box<[Color]> {
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let mut errors = vec![[0.0_f32; 3]; width * height];
+        let mut colors = Vec::with_capacity(width * height);
+
+        for (index, Rgba([r, g, b, alpha])) in image.pixels().copied().enumerate() {
+            let rgb: [u8; 3] = std::array::from_fn(|channel| {
+                ([r, g, b][channel] as f32 + errors[index][channel]).round().clamp(0.0, 255.0) as u8
+            });
+            let color = self.color(rgb, alpha);
+
+            colors.push(color);
+
+            if alpha < u8::MAX || !self.transforms.is_empty() {
+                continue;
+            }
+
+            let displayed = self::displayed_rgb(color);
+            let error: [f32; 3] = std::array::from_fn(|channel| rgb[channel] as f32 - displayed[channel] as f32);
+            let (x, y) = (index % width, index / width);
+            let neighbors = [(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)];
+
+            for (offset_x, offset_y, weight) in neighbors {
+                let (Some(x), y) = (x.checked_add_signed(offset_x), y + offset_y) else { continue };
+
+                if x < width && y < height {
+                    let neighbor = &mut errors[y * width + x];
+
+                    (0 .. 3).for_each(|channel| neighbor[channel] += error[channel] * weight / 16.0);
+                }
+            }
+        }
+
+        colors.into_boxed_slice()
+    }
+}
+
+fn displayed_rgb(color: Color) -> [u8; 3] {
+    match color {
+        Color::Rgb { r, g, b } => [r, g, b],
+        Color::AnsiValue(index) => self::ansi_rgb(index),
+        _ => [0; 3],
+    }
+}
+
+/// Returns a palette of at most `count` colors that represents the given colors, found using median cut.
+///
+/// The colors are repeatedly split at the median of the channel with the widest range, and each resulting group is
+/// represented by its average color.
+pub fn median_cut(colors: Vec<[u8; 3]>, count: usize) -> Box<[[u8; 3]]> {
+    let channel_range = |colors: &[[u8; 3]], channel: usize| {
+        let values = colors.iter().map(|color| color[channel]);
+
+        values.clone().max().unwrap_or(0) - values.min().unwrap_or(0)
+    };
+    let mut groups = vec![colors];
+
+    groups.retain(|group| !group.is_empty());
+
+    while groups.len() < count {
+        let widest = groups
+            .iter()
+            .enumerate()
+            .filter(|(_, group)| group.len() > 1)
+            .flat_map(|(index, group)| (0 .. 3).map(move |channel| (index, channel, channel_range(group, channel))))
+            .max_by_key(|(_, _, range)| *range);
+
+        let Some((index, channel, range)) = widest else { break };
+
+        if range == 0 {
+            break;
+        }
+
+        let group = &mut groups[index];
+
+        group.sort_unstable_by_key(|color| color[channel]);
+
+        let upper = group.split_off(group.len() / 2);
+
+        groups.push(upper);
+    }
+
+    groups
+        .iter()
+        .map(|group| {
+            let totals = group.iter().fold([0_u64; 3], |totals, color| {
+                std::array::from_fn(|channel| totals[channel] + color[channel] as u64)
+            });
+
+            totals.map(|total| (total / group.len() as u64) as u8)
+        })
+        .collect()
+}
+
+fn rgb_distance(lhs: [u8; 3], rhs: [u8; 3]) -> u32 {
+    (0 .. 3).map(|channel| (lhs[channel].abs_diff(rhs[channel]) as u32).pow(2)).sum()
+}
+
+/// Converts the given sRGB channel into linear light, between zero and one.
+pub fn srgb_to_linear(channel: u8) -> f32 {
+    let channel = channel as f32 / u8::MAX as f32;
+
+    if channel <= 0.04045 { channel / 12.92 } else { ((channel + 0.055) / 1.055).powf(2.4) }
+}
+
+/// Converts the given channel in linear light, between zero and one, into sRGB.
+pub fn linear_to_srgb(channel: f32) -> u8 {
+    let channel = channel.clamp(0.0, 1.0);
+    let channel = if channel <= 0.003_130_8 { channel * 12.92 } else { 1.055 * channel.powf(1.0 / 2.4) - 0.055 };
+
+    (channel * u8::MAX as f32).round() as u8
+}
+
+fn rgb_to_lab(rgb: [u8; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb.map(self::srgb_to_linear);
+
+    // Convert to CIE XYZ relative to the D65 white point.
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+
+    let [x, y, z] = [x, y, z]
+        .map(|value| if value > 216.0 / 24389.0 { value.cbrt() } else { (24389.0 / 27.0 * value + 16.0) / 116.0 });
+
+    [116.0 * y - 16.0, 500.0 * (x - y), 200.0 * (y - z)]
+}
+
+fn rgb_to_hsv(rgb: [u8; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb.map(|channel| channel as f32 / u8::MAX as f32);
+    let maximum = r.max(g).max(b);
+    let delta = maximum - r.min(g).min(b);
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if maximum == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if maximum == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let saturation = if maximum == 0.0 { 0.0 } else { delta / maximum };
+
+    [hue, saturation, maximum]
+}
+
+/// Converts a color with a hue in degrees, and a saturation and value between zero and one, into RGB.
+pub fn hsv_to_rgb([hue, saturation, value]: [f32; 3]) -> [u8; 3] {
+    let chroma = value * saturation;
+    let sector = hue.rem_euclid(360.0) / 60.0;
+    let secondary = chroma * (1.0 - (sector.rem_euclid(2.0) - 1.0).abs());
+
+    let [r, g, b] = match sector as u8 {
+        0 => [chroma, secondary, 0.0],
+        1 => [secondary, chroma, 0.0],
+        2 => [0.0, chroma, secondary],
+        3 => [0.0, secondary, chroma],
+        4 => [secondary, 0.0, chroma],
+        _ => [chroma, 0.0, secondary],
+    };
+    let minimum = value - chroma;
+
+    [r, g, b].map(|channel| ((channel + minimum) * u8::MAX as f32).round() as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColorDepth, ColorDistance, ColorPipeline, ColorTransform};
+
+    const PIXEL: [u8; 3] = [200, 60, 30];
+
+    fn pipeline(transforms: &[ColorTransform]) -> ColorPipeline {
+        let mut pipeline = ColorPipeline::new(ColorDepth::TrueColor, ColorDistance::default(), [0; 3]);
+
+        transforms.iter().copied().for_each(|transform| pipeline.push(transform));
+
+        pipeline
+    }
+
+    #[test]
+    fn transform_order_matters() {
+        // Saturation and hue rotation commute, since they change separate components of the same HSV color, whereas
+        // inverting a color changes its saturation.
+        let saturate_first = self::pipeline(&[ColorTransform::Saturate(0.5), ColorTransform::Invert]);
+        let invert_first = self::pipeline(&[ColorTransform::Invert, ColorTransform::Saturate(0.5)]);
+
+        assert_ne!(saturate_first.transform(PIXEL), invert_first.transform(PIXEL));
+
+        let saturate_first = self::pipeline(&[ColorTransform::Saturate(0.5), ColorTransform::RotateHue(120.0)]);
+        let rotate_first = self::pipeline(&[ColorTransform::RotateHue(120.0), ColorTransform::Saturate(0.5)]);
+        let difference = std::iter::zip(saturate_first.transform(PIXEL), rotate_first.transform(PIXEL))
+            .map(|(lhs, rhs)| lhs.abs_diff(rhs))
+            .max();
+
+        assert!(difference <= Some(1), "only rounding should differ between the orders");
+    }
+
+    #[test]
+    fn identity_transforms_do_nothing() {
+        let pipeline = self::pipeline(&[ColorTransform::Saturate(1.0), ColorTransform::RotateHue(0.0)]);
+
+        assert_eq!(pipeline, self::pipeline(&[]));
+        assert_eq!(pipeline.transform(PIXEL), PIXEL);
+        assert_eq!(ColorTransform::Saturate(1.0).apply(PIXEL), PIXEL);
+        assert_eq!(ColorTransform::RotateHue(360.0).apply(PIXEL), PIXEL);
+    }
+
+    #[test]
+    fn median_cut_separates_clusters() {
+        let colors = [[250, 10, 10], [240, 20, 0], [10, 10, 250], [0, 20, 240]];
+        let mut palette = super::median_cut(colors.repeat(4), 2).into_vec();
+
+        // Each cluster is represented by its average color.
+        palette.sort_unstable();
+
+        assert_eq!(palette, [[5, 15, 245], [245, 15, 5]]);
+    }
+
+    #[test]
+    fn median_cut_never_exceeds_the_distinct_colors() {
+        assert_eq!(&*super::median_cut(vec![[1, 2, 3]; 16], 8), [[1, 2, 3]]);
+        assert!(super::median_cut(Vec::new(), 8).is_empty());
+    }
+}
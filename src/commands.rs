@@ -0,0 +1,55 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! The commands run by the program, one for each subcommand, and the helpers that they share.
+
+pub mod bench;
+pub mod export;
+pub mod list_fonts;
+pub mod precompute;
+pub mod preview;
+pub mod probe;
+pub mod view;
+
+use anyhow::Result;
+
+use crate::cache::{FileCache, FontBundle};
+use crate::cli::{RenderArguments, SizeArguments};
+use crate::font::FontFace;
+use crate::measure::{self, RasterSettings};
+
+pub fn font_name(arguments: &RenderArguments) -> Result<Option<Box<str>>> {
+    // Threshold maps choose characters without measuring any font.
+    Ok(match (arguments.mono_threshold_map.is_some(), arguments.import_font_cache.as_deref()) {
+        (true, _) => None,
+        (false, Some(path)) => Some(FontBundle::read(path)?.font_name),
+        (false, None) => Some(self::font_names(&measure::measure_settings(&arguments.measure).0)),
+    })
+}
+
+pub fn font_names(fonts: &[FontFace]) -> Box<str> {
+    fonts.iter().map(FontFace::name).collect::<Vec<_>>().join(", ").into()
+}
+
+pub fn print_fonts(fonts: &[FontFace], cache: &FileCache, raster_settings: RasterSettings) {
+    for font in fonts {
+        println!("font: {}", font.name());
+        println!("path: {}", font.path().map_or_else(|| "embedded".into(), |path| path.display().to_string()));
+        println!("cache: {}", cache.path(&measure::brightness_cache_key(font, raster_settings)).display());
+    }
+}
+
+pub fn resolve_size(arguments: &SizeArguments) -> (u16, u16) {
+    let terminal_size = crossterm::terminal::size().unwrap_or((80, 24));
+
+    (arguments.width.unwrap_or(terminal_size.0), arguments.height.unwrap_or(terminal_size.1))
+}
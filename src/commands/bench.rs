@@ -0,0 +1,62 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Measures the time taken to draw an image.
+
+use std::time::Instant;
+
+use anyhow::Result;
+
+use crate::buffer::RowCache;
+use crate::cli::BenchArguments;
+use crate::render::{ClearMode, ROW_CACHE_CAPACITY};
+use crate::source::SourceImage;
+use crate::{render, source};
+
+pub fn run(arguments: &BenchArguments) -> Result<()> {
+    let draw_settings = render::draw_settings(&arguments.render, ClearMode::All, false)?;
+    let size = super::resolve_size(&arguments.size);
+    let (width, height) = draw_settings.scaled_size(size);
+    let source_image = source::open_source(
+        arguments.path.as_deref(),
+        arguments.test_pattern,
+        arguments.clipboard,
+        (width as u32 / 2, height as u32),
+        arguments.render.page as usize,
+    )?;
+    let mut source_image = match source::open_difference(&arguments.render, (width as u32 / 2, height as u32))? {
+        Some(other_image) => source::difference(&arguments.render, &source_image, &other_image)?,
+        None => source_image,
+    };
+
+    source::trim(&arguments.render, std::slice::from_mut(&mut source_image))?;
+    let source_image = SourceImage::new(source_image, draw_settings.alpha_threshold);
+    let start = Instant::now();
+
+    for _ in 0 .. arguments.iterations {
+        let mut row_cache = RowCache::new(ROW_CACHE_CAPACITY);
+
+        render::render_frame(&draw_settings, &source_image, size, &mut row_cache).queue(
+            &mut std::io::sink(),
+            None,
+            true,
+        )?;
+    }
+
+    let elapsed = start.elapsed();
+
+    println!("size: {}x{}", size.0, size.1);
+    println!("total: {elapsed:.2?}");
+    println!("per frame: {:.2?}", elapsed / arguments.iterations);
+
+    Ok(())
+}
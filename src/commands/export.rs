@@ -0,0 +1,238 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Writes an image drawn as colored text to a file.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::buffer::{FrameBuffer, RowCache};
+use crate::cli::{ExportArguments, MeasureArguments};
+use crate::color::{ColorDepth, ColorTarget};
+use crate::measure::{CharacterMap, MAX_BRIGHTNESS};
+use crate::render::{ClearMode, DrawSettings, ROW_CACHE_CAPACITY};
+use crate::source::SourceImage;
+use crate::{cp437, measure, render, source, svg};
+
+/// The settings that an exported image was drawn with, written beside it by `--write-metadata`.
+#[derive(Debug, Serialize)]
+struct ExportMetadata<'a> {
+    source: Option<&'a Path>,
+    font: Option<Box<str>>,
+    characters: String,
+    width: u16,
+    height: u16,
+    format: ExportFormat,
+    encoding: ExportEncoding,
+    color_depth: Option<ColorDepth>,
+    color_target: ColorTarget,
+}
+
+/// The format in which an exported image is written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Text with color escape sequences, which reproduces the image when printed with `cat`.
+    #[default]
+    Ansi,
+    /// Text without any escape sequences.
+    Text,
+    /// An SVG image that draws each character as text in the measured font.
+    Svg,
+}
+
+/// The character encoding of an exported image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExportEncoding {
+    /// UTF-8, as expected by modern terminals.
+    #[default]
+    #[value(name = "utf-8")]
+    Utf8,
+    /// Code page 437, as expected by DOS and bulletin board systems, which only contains a limited set of characters.
+    Cp437,
+}
+
+pub fn run(arguments: &ExportArguments) -> Result<()> {
+    let draw_settings = render::draw_settings(&arguments.render, ClearMode::None, false)?;
+    let size = draw_settings.scaled_size(super::resolve_size(&arguments.size));
+    // Exports are not bound by the terminal, so the scale is applied to the exported grid rather than to the image.
+    let draw_settings = DrawSettings { scale: 1.0, ..draw_settings };
+    let source_image = source::open_source(
+        arguments.path.as_deref(),
+        arguments.test_pattern,
+        arguments.clipboard,
+        (size.0 as u32 / 2, size.1 as u32),
+        arguments.render.page as usize,
+    )?;
+    let mut source_image = match source::open_difference(&arguments.render, (size.0 as u32 / 2, size.1 as u32))? {
+        Some(other_image) => source::difference(&arguments.render, &source_image, &other_image)?,
+        None => source_image,
+    };
+
+    source::trim(&arguments.render, std::slice::from_mut(&mut source_image))?;
+    anyhow::ensure!(
+        arguments.format == ExportFormat::Svg || !arguments.proportional,
+        "only SVG exports may be packed proportionally"
+    );
+    anyhow::ensure!(
+        arguments.format != ExportFormat::Svg || arguments.encoding == ExportEncoding::Utf8,
+        "SVG exports are always encoded as UTF-8"
+    );
+
+    let source_image = SourceImage::new(source_image, draw_settings.alpha_threshold);
+    let frame = render::render_frame(&draw_settings, &source_image, size, &mut RowCache::new(ROW_CACHE_CAPACITY));
+
+    if arguments.stats {
+        self::write_stats(&mut std::io::stderr().lock(), &frame, &draw_settings.character_map)?;
+    }
+
+    let with_color = arguments.format == ExportFormat::Ansi;
+    // Hyperlinks are escape sequences, so they are only written alongside the other escape sequences.
+    let link = arguments.source_url.as_deref().filter(|_| with_color);
+    let mut text = Vec::new();
+
+    if arguments.format == ExportFormat::Svg {
+        let font_name = super::font_name(&arguments.render)?;
+        let advances = arguments.proportional.then(|| self::glyph_advances(&arguments.render.measure)).transpose()?;
+
+        svg::write(&mut text, &frame, font_name.as_deref(), draw_settings.background, advances.as_ref())?;
+    } else {
+        frame.write_lines(&mut text, with_color, link)?;
+    }
+
+    if arguments.encoding == ExportEncoding::Cp437 {
+        text = String::from_utf8_lossy(&text)
+            .chars()
+            .map(|character| {
+                cp437::encode(character)
+                    .ok_or_else(|| anyhow::anyhow!("the character '{character}' cannot be encoded in code page 437"))
+            })
+            .collect::<Result<_>>()?;
+    }
+
+    match arguments.output.as_deref() {
+        Some(path) => {
+            std::fs::write(path, text)?;
+
+            if arguments.write_metadata {
+                let metadata = ExportMetadata {
+                    source: arguments.path.as_deref(),
+                    font: super::font_name(&arguments.render)?,
+                    characters: draw_settings
+                        .character_map
+                        .ramp()
+                        .into_iter()
+                        .map(|(character, _)| character)
+                        .collect(),
+                    width: size.0,
+                    height: size.1,
+                    format: arguments.format,
+                    encoding: arguments.encoding,
+                    color_depth: draw_settings
+                        .image_color_pipeline(&source_image.image)
+                        .map(|color_pipeline| color_pipeline.depth),
+                    color_target: draw_settings.color_target,
+                };
+                let mut metadata_path = path.as_os_str().to_owned();
+
+                metadata_path.push(".json");
+
+                serde_json::to_writer_pretty(BufWriter::new(File::create(metadata_path)?), &metadata)?;
+            }
+
+            Ok(())
+        }
+        None => {
+            let mut stdout = std::io::stdout().lock();
+
+            stdout.write_all(&text)?;
+            stdout.flush().map_err(Into::into)
+        }
+    }
+}
+
+fn write_stats(writer: &mut impl Write, frame: &FrameBuffer, character_map: &CharacterMap) -> Result<()> {
+    const HISTOGRAM_BUCKETS: usize = 10;
+    const HISTOGRAM_WIDTH: usize = 40;
+
+    let brightnesses: HashMap<char, u16> = character_map.ramp().into_iter().collect();
+    let mut uses: HashMap<char, usize> = HashMap::new();
+
+    for cell in frame.drawn_cells() {
+        *uses.entry(cell.character).or_default() += 1;
+    }
+
+    let total = uses.values().sum::<usize>().max(1);
+    let mut uses: Vec<(char, usize)> = uses.into_iter().collect();
+    let mut histogram = [0_usize; HISTOGRAM_BUCKETS];
+
+    // The most used characters are listed first, and ties are broken by brightness to keep the order stable.
+    uses.sort_unstable_by_key(|(character, count)| {
+        (std::cmp::Reverse(*count), brightnesses.get(character), *character)
+    });
+
+    writeln!(writer, "characters:")?;
+
+    for (character, count) in &uses {
+        writeln!(writer, "  '{character}' {count:>8} {:>5.1}%", *count as f64 * 100.0 / total as f64)?;
+
+        // The fallback character is never measured, so it has no brightness to count towards.
+        if let Some(brightness) = brightnesses.get(character) {
+            let bucket = *brightness as usize * HISTOGRAM_BUCKETS / (MAX_BRIGHTNESS as usize + 1);
+
+            histogram[bucket] += count;
+        }
+    }
+
+    writeln!(writer, "brightnesses:")?;
+
+    let maximum = histogram.iter().copied().max().unwrap_or(0).max(1);
+
+    for (bucket, count) in histogram.into_iter().enumerate() {
+        let (start, end) = (bucket * 100 / HISTOGRAM_BUCKETS, (bucket + 1) * 100 / HISTOGRAM_BUCKETS);
+        let bar = "█".repeat((count * HISTOGRAM_WIDTH).div_ceil(maximum));
+
+        writeln!(writer, "  {start:>3}% - {end:>3}% {count:>8} {bar}")?;
+    }
+
+    writer.flush().map_err(Into::into)
+}
+
+fn glyph_advances(arguments: &MeasureArguments) -> Result<HashMap<char, f32>> {
+    let (fonts, charset, _) = measure::measure_settings(arguments);
+    let mut advances = HashMap::new();
+
+    for font in &fonts {
+        let font_data = font.data()?;
+        let font_ref = measure::font_ref(font, &font_data)?;
+        // Scaling to a size of one measures each advance relative to the font size.
+        let metrics = font_ref.glyph_metrics(&[]).scale(1.0);
+
+        for character in charset.characters.iter().copied() {
+            let glyph_id = font_ref.charmap().map(character);
+            let advance = metrics.advance_width(glyph_id);
+
+            if glyph_id != 0 && advance > 0.0 {
+                advances.entry(character).or_insert(advance);
+            }
+        }
+    }
+
+    Ok(advances)
+}
@@ -0,0 +1,43 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Lists the font families that may be given to `--font`.
+
+use std::collections::BTreeSet;
+use std::io::Write;
+
+use anyhow::Result;
+use fontconfig::{ObjectSet, Pattern};
+
+use crate::measure::FONT_CONFIG;
+
+pub fn run() -> Result<()> {
+    let Some(font_config) = FONT_CONFIG.as_ref() else {
+        return Ok(());
+    };
+    let mut objects = ObjectSet::new(font_config);
+
+    objects.add(fontconfig::FC_FAMILY);
+
+    let families: BTreeSet<Box<str>> = fontconfig::list_fonts(&Pattern::new(font_config), Some(&objects))
+        .iter()
+        .filter_map(|pattern| pattern.get_string(fontconfig::FC_FAMILY).map(Into::into))
+        .collect();
+
+    let mut stdout = std::io::stdout().lock();
+
+    for family in families {
+        writeln!(stdout, "{family}")?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Measures and caches the brightness of each character.
+
+use anyhow::Result;
+
+use crate::cache::FontBundle;
+use crate::cli::PrecomputeArguments;
+use crate::measure::{self, Hinting};
+
+pub fn run(arguments: &PrecomputeArguments) -> Result<()> {
+    let (fonts, charset, raster_settings) = measure::measure_settings(&arguments.measure);
+    let mut cache = measure::glyph_cache();
+
+    if arguments.dry_run {
+        super::print_fonts(&fonts, &cache, raster_settings);
+
+        return Ok(());
+    }
+
+    let brightnesses = measure::compute_brightnesses(
+        &mut cache,
+        &fonts,
+        &charset,
+        raster_settings,
+        arguments.measure.all_glyphs,
+        arguments.measure.normalize_percentile,
+        arguments.measure.parallelism(),
+    )?;
+
+    if let Some(path) = arguments.export_font_cache.as_deref() {
+        let bundle = FontBundle {
+            font_name: super::font_names(&fonts),
+            glyph_size: raster_settings.size,
+            glyph_scale: raster_settings.scale,
+            hinted: raster_settings.hinting == Hinting::Full,
+            subpixel: raster_settings.subpixel,
+            brightnesses: brightnesses.clone(),
+        };
+
+        bundle.write(path)?;
+    }
+
+    super::print_fonts(&fonts, &cache, raster_settings);
+    println!("characters: {}", brightnesses.len());
+
+    Ok(())
+}
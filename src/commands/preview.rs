@@ -0,0 +1,51 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Prints an image that exactly fills a file manager's preview pane.
+
+use std::io::Write;
+
+use anyhow::Result;
+
+use crate::buffer::RowCache;
+use crate::cli::PreviewArguments;
+use crate::render::{ClearMode, ROW_CACHE_CAPACITY};
+use crate::source::SourceImage;
+use crate::{render, source};
+
+pub fn run(arguments: &PreviewArguments) -> Result<()> {
+    // Previews are printed into a pane that the terminal does not know about, so it is never queried or cleared.
+    let draw_settings = render::draw_settings(&arguments.render, ClearMode::None, false)?;
+    let size = (arguments.width.max(1), arguments.height.max(1));
+    let (width, height) = draw_settings.scaled_size(size);
+    let target_size = (width as u32 / 2, height as u32);
+    let source_image = source::open_image(&arguments.path, target_size, arguments.render.page as usize)?;
+    let mut source_image = match source::open_difference(&arguments.render, target_size)? {
+        Some(other_image) => source::difference(&arguments.render, &source_image, &other_image)?,
+        None => source_image,
+    };
+
+    source::trim(&arguments.render, std::slice::from_mut(&mut source_image))?;
+
+    let source_image = SourceImage::new(source_image, draw_settings.alpha_threshold);
+    let frame = render::render_frame(&draw_settings, &source_image, size, &mut RowCache::new(ROW_CACHE_CAPACITY));
+    let mut text = Vec::new();
+
+    frame.write_lines(&mut text, true, None)?;
+    // The newline that ends the last row would scroll the pane by a row, so only the rows between lines are ended.
+    text.pop();
+
+    let mut stdout = std::io::stdout().lock();
+
+    stdout.write_all(&text)?;
+    stdout.flush().map_err(Into::into)
+}
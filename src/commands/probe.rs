@@ -0,0 +1,56 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Reports the size, color support, graphics protocols and background color detected for the terminal.
+
+use anyhow::Result;
+
+use crate::terminal;
+
+pub fn run() -> Result<()> {
+    let unknown = || "unknown".to_string();
+    let window_size = crossterm::terminal::window_size().ok();
+    // Terminals that do not report their size in pixels report it as zero instead.
+    let pixels =
+        window_size.as_ref().filter(|size| size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0);
+    let attributes = terminal::device_attributes();
+
+    let colors = match crossterm::style::available_color_count() {
+        u16::MAX => "true color",
+        256 .. => "256 colors",
+        _ => "16 colors",
+    };
+    let yes_or_no = |supported: bool| if supported { "yes" } else { "no" }.to_string();
+
+    println!("size: {}", window_size.as_ref().map_or_else(unknown, |size| format!("{}x{}", size.columns, size.rows)));
+    println!(
+        "pixels: {}",
+        pixels.map_or_else(unknown, |size| {
+            let (cell_width, cell_height) = (size.width / size.columns, size.height / size.rows);
+
+            format!("{}x{} ({cell_width}x{cell_height} per cell)", size.width, size.height)
+        })
+    );
+    println!("colors: {colors}");
+    println!(
+        "sixel: {}",
+        attributes
+            .map_or_else(unknown, |attributes| yes_or_no(attributes.iter().skip(1).any(|attribute| *attribute == 4)))
+    );
+    println!("kitty graphics: {}", terminal::supports_kitty_graphics().map_or_else(unknown, yes_or_no));
+    println!(
+        "background: {}",
+        terminal::background_color().map_or_else(unknown, |[r, g, b]| format!("#{r:02x}{g:02x}{b:02x}"))
+    );
+
+    Ok(())
+}
@@ -0,0 +1,558 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Draws an image in the terminal until a quit key is pressed.
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::mem::ManuallyDrop;
+use std::os::fd::FromRawFd;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use crossterm::cursor::MoveTo;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::{Print, ResetColor};
+use image::DynamicImage;
+use signal_hook::consts::{SIGCONT, SIGHUP, SIGINT, SIGTERM, SIGTSTP};
+use signal_hook::iterator::Signals;
+
+use crate::buffer::{FrameBuffer, RowCache};
+use crate::cli::ViewArguments;
+use crate::render::{Anchor, BadgeStyle, ClearMode, DrawSettings, ROW_CACHE_CAPACITY};
+use crate::source::SourceImage;
+use crate::{measure, progressive, render, source};
+
+const EVENT_POLL_TIMEOUT: Duration = Duration::from_millis(1_000 / 60);
+
+const MAX_EVENT_POLL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The standard stream that images are drawn to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum RenderTarget {
+    /// Standard output.
+    #[default]
+    Stdout,
+    /// Standard error.
+    Stderr,
+}
+
+impl RenderTarget {
+    pub fn writer(self) -> Box<dyn Write> {
+        match self {
+            Self::Stdout => Box::new(std::io::stdout().lock()),
+            // Standard error is not buffered at all, which would write every escape sequence separately.
+            Self::Stderr => Box::new(BufWriter::new(std::io::stderr().lock())),
+        }
+    }
+
+    pub const fn file_descriptor(self) -> libc::c_int {
+        match self {
+            Self::Stdout => libc::STDOUT_FILENO,
+            Self::Stderr => libc::STDERR_FILENO,
+        }
+    }
+}
+
+pub fn run(arguments: &ViewArguments) -> Result<()> {
+    if arguments.show_font {
+        let (fonts, _, raster_settings) = measure::measure_settings(&arguments.render.measure);
+
+        super::print_fonts(&fonts, &measure::glyph_cache(), raster_settings);
+
+        return Ok(());
+    }
+
+    let clear_mode =
+        arguments.clear.unwrap_or(if arguments.badge.is_some() { ClearMode::None } else { ClearMode::All });
+    // Querying the terminal's background color briefly enters raw mode and reads its reply from standard input, so it
+    // is skipped along with everything else, and would otherwise consume the paths of a session.
+    let query_terminal = !arguments.no_raw && !arguments.stdin_commands;
+
+    if query_terminal {
+        self::restore_on_signal(arguments.render_to)?;
+    }
+
+    let mut draw_settings = render::draw_settings(&arguments.render, clear_mode, query_terminal)?;
+    // Each terminal cell is drawn using half as many source pixels horizontally as it is vertically.
+    let (width, height) = draw_settings.scaled_size(crossterm::terminal::size().unwrap_or((80, 24)));
+    let target_size = (width as u32 / 2, height as u32);
+
+    if arguments.stdin_commands {
+        return self::stdin_session(arguments, &draw_settings, target_size);
+    }
+
+    // Images that are drawn interactively may be previewed while they are decoded in full, which is redrawn over it.
+    let preview_path = arguments
+        .path
+        .as_deref()
+        .filter(|_| !arguments.dry_size && arguments.badge.is_none() && !arguments.no_raw)
+        .filter(|_| arguments.render.diff.is_none() && arguments.render.page == 1);
+    let mut pending_frames: Option<Receiver<Result<Box<[DynamicImage]>>>> = None;
+
+    let frames = match preview_path.and_then(progressive::preview) {
+        Some(mut preview) => {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            let path = preview_path.expect("a preview requires a path").to_path_buf();
+
+            std::thread::spawn(move || sender.send(source::load_frames(&path, target_size, 1)));
+            source::trim(&arguments.render, std::slice::from_mut(&mut preview))?;
+            pending_frames = Some(receiver);
+
+            Box::new([preview])
+        }
+        None => self::view_frames(arguments, arguments.path.as_deref(), target_size)?,
+    };
+    let mut frames = SourceImage::frames(frames, draw_settings.alpha_threshold);
+
+    if arguments.dry_size {
+        let terminal_size = crossterm::terminal::size().unwrap_or((80, 24));
+        let (width, height) = render::scaled_image_size(&draw_settings, &frames[0].image, terminal_size);
+
+        println!("{width}x{height}");
+
+        return Ok(());
+    }
+
+    if let Some(badge_style) = arguments.badge {
+        let maximum_width = crossterm::terminal::size().map_or(80, |(width, _)| width);
+
+        anyhow::ensure!(
+            badge_style == BadgeStyle::HalfBlocks || !arguments.half_block_dither,
+            "only half-block badges may be dithered"
+        );
+
+        return render::draw_badge(
+            &mut arguments.render_to.writer(),
+            &draw_settings,
+            &frames[0].image,
+            badge_style,
+            maximum_width,
+            arguments.half_block_dither,
+        );
+    }
+
+    if arguments.no_raw {
+        let (width, height) = crossterm::terminal::size().unwrap_or((80, 24));
+        // The last row is left free, so that the image is not scrolled out of view by the shell's prompt.
+        let frame = render::render_frame(
+            &draw_settings,
+            &frames[0],
+            (width, height.saturating_sub(1).max(1)),
+            &mut RowCache::new(ROW_CACHE_CAPACITY),
+        );
+        let mut stdout = arguments.render_to.writer();
+
+        self::inline_frame(&frame, arguments.cursor_below).write_lines(&mut stdout, true, None)?;
+
+        return stdout.flush().map_err(Into::into);
+    }
+
+    crossterm::terminal::enable_raw_mode()?;
+
+    let mut stdout = arguments.render_to.writer();
+
+    // Raw mode disables the terminal's own handling of Ctrl-Z, so both it and an external `SIGTSTP` are handled
+    // manually in order to restore the terminal before the process is suspended.
+    let suspend_requested = Arc::new(AtomicBool::new(false));
+    let resumed = Arc::new(AtomicBool::new(false));
+
+    signal_hook::flag::register(SIGTSTP, Arc::clone(&suspend_requested))?;
+    signal_hook::flag::register(SIGCONT, Arc::clone(&resumed))?;
+
+    let mut frame_index = 0;
+    let mut previous_frame = None;
+    let mut row_cache = RowCache::new(ROW_CACHE_CAPACITY);
+    let mut show_help = false;
+    let mut reload_error_lines: Option<Box<[Box<str>]>> = None;
+    let help_lines = self::help_lines(&arguments.quit_keys, arguments.scroll);
+    // Scrolled images are drawn in full once, and then each scroll only changes which of their rows are visible.
+    let mut scroll_frame: Option<FrameBuffer> = None;
+    let mut scroll_offset = 0_u16;
+    let show_fps_counter = arguments.fps_counter && frames.len() > 1;
+    let mut redraw = true;
+    let mut poll_timeout = EVENT_POLL_TIMEOUT;
+
+    let start = Instant::now();
+    // The times at which frames were advanced within the last second, used to measure the displayed frame rate.
+    let mut frame_times = VecDeque::from([start]);
+    let frame_duration = Duration::from_secs_f32(arguments.fps.recip());
+    let mut next_frame = start + frame_duration;
+    // Input is handled as soon as it arrives, but the redraws that it requests are deferred to honor the cap.
+    let redraw_interval = arguments.max_fps.map(|max_fps| Duration::from_secs_f32(max_fps.recip()));
+    let mut next_redraw = start;
+
+    loop {
+        if arguments.timeout.is_some_and(|timeout| start.elapsed() >= timeout) {
+            break;
+        }
+
+        if suspend_requested.swap(false, AtomicOrdering::Relaxed) {
+            self::restore_terminal(&mut stdout)?;
+
+            signal_hook::low_level::emulate_default_handler(SIGTSTP)?;
+        }
+
+        if resumed.swap(false, AtomicOrdering::Relaxed) {
+            crossterm::terminal::enable_raw_mode()?;
+
+            // The terminal's contents may have been replaced while suspended, so nothing can be assumed about them.
+            previous_frame = None;
+            redraw = true;
+        }
+
+        if let Some(receiver) = &pending_frames
+            && let Ok(result) = receiver.try_recv()
+        {
+            match result.and_then(|mut loaded_frames| {
+                source::trim(&arguments.render, &mut loaded_frames).map(|()| loaded_frames)
+            }) {
+                Ok(loaded_frames) => frames = SourceImage::frames(loaded_frames, draw_settings.alpha_threshold),
+                Err(error) => reload_error_lines = Some(Box::new([format!("failed to decode: {error}").into()])),
+            }
+
+            pending_frames = None;
+            scroll_frame = None;
+            redraw = true;
+        }
+
+        if frames.len() > 1 && Instant::now() >= next_frame {
+            frame_index = (frame_index + 1) % frames.len();
+            next_frame += frame_duration;
+            scroll_frame = None;
+            redraw = true;
+
+            let now = Instant::now();
+
+            frame_times.push_back(now);
+
+            while frame_times.front().is_some_and(|time| now.duration_since(*time) > Duration::from_secs(1)) {
+                frame_times.pop_front();
+            }
+        }
+
+        if redraw && Instant::now() >= next_redraw {
+            let mut overlays = Vec::with_capacity(2);
+            let fps_counter_lines;
+
+            if show_fps_counter {
+                fps_counter_lines = self::fps_counter_lines(&frame_times, arguments.fps, (frame_index, frames.len()));
+
+                overlays.push((Anchor::TopRight, &fps_counter_lines[..]));
+            }
+            if show_help {
+                overlays.push((Anchor::Center, &*help_lines));
+            }
+            if let Some(reload_error_lines) = &reload_error_lines {
+                overlays.push((Anchor::BottomLeft, reload_error_lines));
+            }
+
+            let draw_start = Instant::now();
+
+            if let Some(redraw_interval) = redraw_interval {
+                next_redraw = draw_start + redraw_interval;
+            }
+
+            let terminal_size = crossterm::terminal::size()?;
+
+            if arguments.scroll {
+                let source_image = &frames[frame_index];
+                let scroll_frame = scroll_frame.get_or_insert_with(|| {
+                    let size = render::scroll_size(&source_image.image, terminal_size.0);
+
+                    render::render_frame(&draw_settings, source_image, size, &mut row_cache)
+                });
+
+                scroll_offset = scroll_offset.min(scroll_frame.size().1.saturating_sub(terminal_size.1));
+
+                let frame = scroll_frame.rows(scroll_offset, terminal_size.1);
+
+                render::present_frame(&mut stdout, &draw_settings, frame, &mut previous_frame, &overlays)?;
+            } else {
+                render::draw_image(
+                    &mut stdout,
+                    &draw_settings,
+                    &frames[frame_index],
+                    terminal_size,
+                    &mut previous_frame,
+                    &mut row_cache,
+                    &overlays,
+                )?;
+            }
+
+            if let Some(timing_budget) = arguments.timing_budget
+                && draw_start.elapsed() > timing_budget
+                && let Some(quality) = draw_settings.quality.reduced()
+            {
+                draw_settings.quality = quality;
+                scroll_frame = None;
+            }
+
+            redraw = false;
+        }
+
+        // Static images are only redrawn in response to events, so polling is gradually slowed while idle.
+        let timeout =
+            arguments.timeout.map_or(poll_timeout, |timeout| poll_timeout.min(timeout.saturating_sub(start.elapsed())));
+        let timeout = if redraw { timeout.min(next_redraw.saturating_duration_since(Instant::now())) } else { timeout };
+        let event = crossterm::event::poll(timeout)?.then(crossterm::event::read).transpose()?;
+
+        poll_timeout = if event.is_some() || frames.len() > 1 || pending_frames.is_some() {
+            EVENT_POLL_TIMEOUT
+        } else {
+            (poll_timeout * 2).min(MAX_EVENT_POLL_TIMEOUT)
+        };
+
+        match event {
+            Some(Event::Key(KeyEvent { code: KeyCode::Char('c'), modifiers: KeyModifiers::CONTROL, .. })) => break,
+            Some(Event::Key(KeyEvent { code: KeyCode::Char('z'), modifiers: KeyModifiers::CONTROL, .. })) => {
+                suspend_requested.store(true, AtomicOrdering::Relaxed);
+            }
+            Some(Event::Key(KeyEvent { code, .. })) if arguments.quit_keys.contains(&code) => break,
+            Some(Event::Key(KeyEvent { code: KeyCode::Char('?'), .. })) => {
+                show_help = !show_help;
+                redraw = true;
+            }
+            // The previous frames are kept if reloading fails, since the file may be partway through being written.
+            Some(Event::Key(KeyEvent { code: KeyCode::Char('r'), .. })) => {
+                match self::view_frames(arguments, arguments.path.as_deref(), target_size) {
+                    Ok(reloaded_frames) => {
+                        frames = SourceImage::frames(reloaded_frames, draw_settings.alpha_threshold);
+                        frame_index %= frames.len();
+                        scroll_frame = None;
+                        reload_error_lines = None;
+                    }
+                    Err(error) => reload_error_lines = Some(Box::new([format!("failed to reload: {error}").into()])),
+                }
+
+                redraw = true;
+            }
+            Some(Event::Key(KeyEvent { code, .. })) if arguments.scroll => {
+                let page_height = crossterm::terminal::size()?.1;
+
+                scroll_offset = match code {
+                    KeyCode::Up => scroll_offset.saturating_sub(1),
+                    KeyCode::Down => scroll_offset.saturating_add(1),
+                    KeyCode::PageUp => scroll_offset.saturating_sub(page_height),
+                    KeyCode::PageDown => scroll_offset.saturating_add(page_height),
+                    KeyCode::Home => 0,
+                    // The offset is clamped to the last page when drawn, since the image's height is not known here.
+                    KeyCode::End => u16::MAX,
+                    _ => continue,
+                };
+                redraw = true;
+            }
+            Some(Event::Resize(..)) => {
+                scroll_frame = None;
+                redraw = true;
+            }
+            _ => {}
+        }
+    }
+
+    // The frame is drawn from the top of the terminal, so the row below it is also the number of rows that it covers.
+    if arguments.cursor_below
+        && let Some(frame) = &previous_frame
+    {
+        crossterm::queue!(stdout, MoveTo(0, frame.drawn_height().saturating_sub(1)))?;
+    }
+
+    self::restore_terminal(&mut stdout)?;
+
+    if arguments.verbose && arguments.timing_budget.is_some() {
+        eprintln!("info: images were drawn at {} quality to meet the timing budget", draw_settings.quality.name());
+    }
+
+    Ok(())
+}
+
+fn view_frames(arguments: &ViewArguments, path: Option<&Path>, target_size: (u32, u32)) -> Result<Box<[DynamicImage]>> {
+    let frames = if arguments.test_pattern || arguments.clipboard {
+        let source_image = source::open_source(
+            None,
+            arguments.test_pattern,
+            arguments.clipboard,
+            target_size,
+            arguments.render.page as usize,
+        )?;
+
+        Box::new([source_image])
+    } else {
+        let path = path.expect("a path is required without a test pattern or the clipboard");
+
+        source::load_frames(path, target_size, arguments.render.page as usize)?
+    };
+
+    let mut frames = match source::open_difference(&arguments.render, target_size)? {
+        Some(other_image) => frames
+            .iter()
+            .map(|frame| source::difference(&arguments.render, frame, &other_image))
+            .collect::<Result<_>>()?,
+        None => frames,
+    };
+
+    source::trim(&arguments.render, &mut frames)?;
+
+    Ok(frames)
+}
+
+fn stdin_session(arguments: &ViewArguments, draw_settings: &DrawSettings, target_size: (u32, u32)) -> Result<()> {
+    let mut row_cache = RowCache::new(ROW_CACHE_CAPACITY);
+
+    for line in std::io::stdin().lines() {
+        let line = line?;
+        let path = line.trim();
+
+        if path == "q" {
+            break;
+        } else if path.is_empty() {
+            continue;
+        }
+
+        // A file that cannot be drawn is reported without ending the session, since the next one may be drawn fine.
+        let frames = match self::view_frames(arguments, Some(Path::new(path)), target_size) {
+            Ok(frames) => frames,
+            Err(error) => {
+                eprintln!("{path}: {error}");
+
+                continue;
+            }
+        };
+
+        let (width, height) = crossterm::terminal::size().unwrap_or((80, 24));
+        let source_image = SourceImage::new(frames.into_vec().swap_remove(0), draw_settings.alpha_threshold);
+        let frame = render::render_frame(
+            draw_settings,
+            &source_image,
+            (width, height.saturating_sub(1).max(1)),
+            &mut row_cache,
+        );
+        let mut stdout = arguments.render_to.writer();
+
+        draw_settings.clear_mode.queue(&mut stdout)?;
+
+        // Clearing the whole terminal leaves the cursor where it was, so the image is drawn from the top instead.
+        if draw_settings.clear_mode == ClearMode::All {
+            crossterm::queue!(stdout, MoveTo(0, 0))?;
+        }
+
+        self::inline_frame(&frame, arguments.cursor_below).write_lines(&mut stdout, true, None)?;
+        stdout.flush()?;
+    }
+
+    Ok(())
+}
+
+fn inline_frame(frame: &FrameBuffer, cursor_below: bool) -> Cow<'_, FrameBuffer> {
+    // A line is written for every row of the frame, so the rows below the image are dropped to end directly below it.
+    if cursor_below { Cow::Owned(frame.rows(0, frame.drawn_height())) } else { Cow::Borrowed(frame) }
+}
+
+fn restore_on_signal(render_target: RenderTarget) -> Result<()> {
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP])?;
+
+    // Raw mode keeps Ctrl-C from raising `SIGINT`, but a signal sent by another process would otherwise end the
+    // program with the terminal still in raw mode, wherever it happened to be, such as in the middle of drawing.
+    std::thread::spawn(move || {
+        // The stream is locked for as long as frames are drawn, so the reset is written to its file descriptor
+        // directly, which is never closed, since the stream still owns it.
+        let mut output = ManuallyDrop::new(unsafe { File::from_raw_fd(render_target.file_descriptor()) });
+
+        if let Some(signal) = self::restore_after_signal(&mut signals, &mut *output) {
+            std::process::exit(128 + signal);
+        }
+    });
+
+    Ok(())
+}
+
+/// Waits for the first of the given signals, then restores the terminal by writing to the given output, returning the
+/// signal that was received.
+fn restore_after_signal(signals: &mut Signals, output: &mut impl Write) -> Option<i32> {
+    let signal = signals.forever().next()?;
+
+    _ = crossterm::terminal::disable_raw_mode();
+    _ = crossterm::execute!(output, ResetColor, Print('\n'));
+
+    Some(signal)
+}
+
+fn restore_terminal(stdout: &mut impl Write) -> Result<()> {
+    crossterm::terminal::disable_raw_mode()?;
+
+    crossterm::execute!(stdout, ResetColor, Print('\n')).map_err(Into::into)
+}
+
+fn help_lines(quit_keys: &[KeyCode], scroll: bool) -> Box<[Box<str>]> {
+    let quit_keys = quit_keys.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ");
+    let mut bindings = vec![(&*quit_keys, "quit"), ("Ctrl-C", "quit"), ("Ctrl-Z", "suspend"), ("r", "reload")];
+
+    if scroll {
+        bindings.extend([
+            ("Up, Down", "scroll by a row"),
+            ("PageUp, PageDown", "scroll by a page"),
+            ("Home, End", "scroll to the top or bottom"),
+        ]);
+    }
+
+    bindings.push(("?", "toggle this help"));
+    let key_width = bindings.iter().map(|(keys, _)| keys.chars().count()).max().unwrap_or(0);
+
+    let lines = bindings.into_iter().map(|(keys, action)| format!("{keys:<key_width$}  {action}").into_boxed_str());
+
+    ["Key bindings".into(), "".into()].into_iter().chain(lines).collect()
+}
+
+fn fps_counter_lines(
+    frame_times: &VecDeque<Instant>,
+    target_fps: f32,
+    (index, count): (usize, usize),
+) -> [Box<str>; 2] {
+    let measured_fps = match (frame_times.front(), frame_times.back()) {
+        (Some(first), Some(last)) if first < last => (frame_times.len() - 1) as f32 / (*last - *first).as_secs_f32(),
+        _ => 0.0,
+    };
+
+    [format!("{measured_fps:.1} / {target_fps:.1} fps").into(), format!("frame {} / {count}", index + 1).into()]
+}
+
+#[cfg(test)]
+mod tests {
+    use signal_hook::consts::{SIGINT, SIGTERM};
+    use signal_hook::iterator::Signals;
+
+    #[test]
+    fn signals_restore_the_terminal() {
+        for signal in [SIGINT, SIGTERM] {
+            let mut signals = Signals::new([signal]).expect("the handler should be registered");
+            let restorer = std::thread::spawn(move || {
+                let mut output = Vec::new();
+
+                (super::restore_after_signal(&mut signals, &mut output), output)
+            });
+
+            signal_hook::low_level::raise(signal).expect("the signal should be raised");
+
+            let (received, output) = restorer.join().expect("the terminal should be restored");
+
+            assert_eq!(received, Some(signal));
+            assert_eq!(output, b"\x1B[0m\n");
+        }
+    }
+}
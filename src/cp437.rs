@@ -0,0 +1,69 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Encoding of text as code page 437.
+//!
+//! Code page 437 is the character set of the original IBM PC, and remains the expected encoding of ANSI art made for
+//! DOS and bulletin board systems. The lower half is shared with ASCII, while the upper half contains accented
+//! letters, box drawing characters, shades, and mathematical symbols.
+
+/// The characters encoded by each byte of the upper half of the code page, starting at `0x80`.
+const UPPER_HALF: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', //
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', //
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', //
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐', //
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', //
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', //
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', //
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{A0}',
+];
+
+/// Returns the byte that encodes the given character, or [`None`] if the code page does not contain it.
+///
+/// Control characters are encoded as themselves rather than as the symbols that the code page draws for them, so
+/// that escape sequences and line endings are preserved.
+pub fn encode(character: char) -> Option<u8> {
+    if character.is_ascii() {
+        return Some(character as u8);
+    }
+    // The house symbol occupies the position of the ASCII delete character.
+    if character == '⌂' {
+        return Some(0x7F);
+    }
+
+    UPPER_HALF.iter().position(|encoded| *encoded == character).map(|index| 0x80 + index as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn control_characters_are_encoded_as_themselves() {
+        for byte in (0x00 ..= 0x1F).chain([0x7F]) {
+            assert_eq!(super::encode(byte as char), Some(byte));
+        }
+
+        // The symbols that the code page draws in place of control characters are not encoded.
+        assert_eq!(super::encode('☺'), None);
+        assert_eq!(super::encode('⌂'), Some(0x7F));
+    }
+
+    #[test]
+    fn box_drawing_characters_are_encoded() {
+        for (character, byte) in [('░', 0xB0), ('│', 0xB3), ('╬', 0xCE), ('┌', 0xDA), ('█', 0xDB), ('▀', 0xDF)]
+        {
+            assert_eq!(super::encode(character), Some(byte), "'{character}' is encoded incorrectly");
+        }
+
+        assert_eq!(super::encode('╭'), None);
+    }
+}
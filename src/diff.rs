@@ -0,0 +1,75 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Differencing of images.
+//!
+//! The difference between two images of the same size is the absolute difference of each of their color channels,
+//! which is black wherever the images match. It may instead be drawn as a heat map that ranges from black through red
+//! and yellow to white as the difference grows, which makes small changes far easier to spot.
+
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+/// Returns the per-pixel absolute difference between the given images, which must have the same dimensions.
+///
+/// Each pixel is as opaque as the more opaque of the two pixels it was computed from.
+pub fn difference(lhs: &DynamicImage, rhs: &DynamicImage, heat_map: bool) -> DynamicImage {
+    assert_eq!(lhs.dimensions(), rhs.dimensions(), "only images of the same size may be differenced");
+
+    let (lhs, rhs) = (lhs.to_rgba8(), rhs.to_rgba8());
+
+    let image = RgbaImage::from_fn(lhs.width(), lhs.height(), |x, y| {
+        let (Rgba(lhs), Rgba(rhs)) = (lhs.get_pixel(x, y), rhs.get_pixel(x, y));
+        let alpha = lhs[3].max(rhs[3]);
+        let difference: [u8; 3] = std::array::from_fn(|channel| lhs[channel].abs_diff(rhs[channel]));
+
+        let [red, green, blue] =
+            if heat_map { self::heat_color(difference.into_iter().max().unwrap_or(0)) } else { difference };
+
+        Rgba([red, green, blue, alpha])
+    });
+
+    DynamicImage::ImageRgba8(image)
+}
+
+fn heat_color(value: u8) -> [u8; 3] {
+    // Each channel ramps up in turn across a third of the range, starting with red.
+    let heat = value as f32 / u8::MAX as f32 * 3.0;
+
+    std::array::from_fn(|channel| ((heat - channel as f32).clamp(0.0, 1.0) * u8::MAX as f32).round() as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Rgba, RgbaImage};
+
+    fn image(pixel: [u8; 4]) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 2, Rgba(pixel)))
+    }
+
+    #[test]
+    fn differences_are_absolute() {
+        let difference = super::difference(&self::image([200, 50, 0, 255]), &self::image([50, 200, 0, 128]), false);
+
+        assert_eq!(difference.to_rgba8().get_pixel(0, 0).0, [150, 150, 0, 255]);
+    }
+
+    #[test]
+    fn heat_maps_range_from_black_to_white() {
+        let black = self::image([0, 0, 0, 255]);
+        let pixel = |other: [u8; 4]| super::difference(&black, &self::image(other), true).to_rgba8().get_pixel(0, 0).0;
+
+        assert_eq!(pixel([0, 0, 0, 255]), [0, 0, 0, 255]);
+        assert_eq!(pixel([85, 0, 0, 255]), [255, 0, 0, 255]);
+        assert_eq!(pixel([0, 170, 0, 255]), [255, 255, 0, 255]);
+        assert_eq!(pixel([0, 0, 255, 255]), [255, 255, 255, 255]);
+    }
+}
@@ -0,0 +1,78 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Errors produced while loading images and measuring fonts.
+//!
+//! The program reports its errors using `anyhow`, but the modules that load images and measure fonts instead return
+//! an [`Error`] that distinguishes the kind of failure, so that their callers may handle each kind differently.
+
+use std::fmt::Display;
+
+/// A result whose error is an [`Error`] by default.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// An error produced while loading images or measuring fonts.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Reading or writing a file failed.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// An image could not be decoded.
+    #[error("{0}")]
+    Decode(Box<dyn std::error::Error + Send + Sync>),
+    /// The clipboard could not be read, or does not contain an image.
+    #[cfg(feature = "clipboard")]
+    #[error("{0}")]
+    Clipboard(Box<str>),
+    /// A font could not be found or read.
+    #[error("{0}")]
+    Font(Box<str>),
+    /// The cache of glyph measurements could not be read or written.
+    #[error("failed to access the glyph cache: {0}")]
+    Cache(serde_json::Error),
+    /// A font bundle could not be read or written.
+    #[error("failed to access the font bundle: {0}")]
+    Bundle(serde_json::Error),
+    /// The threads used to measure glyphs could not be started.
+    #[error("failed to start the measurement threads: {0}")]
+    Threads(#[from] rayon::ThreadPoolBuildError),
+}
+
+impl Error {
+    /// Creates a decoding error with the given message.
+    pub fn decode(message: impl Display) -> Self {
+        Self::Decode(message.to_string().into())
+    }
+
+    /// Creates a clipboard error with the given message.
+    #[cfg(feature = "clipboard")]
+    pub fn clipboard(message: impl Display) -> Self {
+        Self::Clipboard(message.to_string().into_boxed_str())
+    }
+
+    /// Creates a font error with the given message.
+    pub fn font(message: impl Display) -> Self {
+        Self::Font(message.to_string().into_boxed_str())
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(error: image::ImageError) -> Self {
+        Self::Decode(Box::new(error))
+    }
+}
+
+impl From<tiff::TiffError> for Error {
+    fn from(error: tiff::TiffError) -> Self {
+        Self::Decode(Box::new(error))
+    }
+}
@@ -0,0 +1,67 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Font faces whose glyphs are measured.
+//!
+//! Faces are usually installed on the system and found through fontconfig, but a copy of DejaVu Sans Mono is also
+//! compiled into the binary, so that glyphs may still be measured on systems where no fonts are installed at all.
+
+use std::borrow::Cow;
+use std::path::Path;
+
+use fontconfig::Font;
+
+/// The data of the font face compiled into the binary.
+const EMBEDDED_DATA: &[u8] = include_bytes!("../assets/DejaVuSansMono.ttf");
+
+/// A font face whose glyphs may be measured.
+pub enum FontFace {
+    /// A face installed on the system.
+    Installed(Font),
+    /// The face compiled into the binary.
+    Embedded,
+}
+
+impl FontFace {
+    /// Returns the name of this face, which is unique to the face and identifies its cached measurements.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Installed(font) => &font.name,
+            // The installed copy of the same font may be of a different version, so the two are kept apart.
+            Self::Embedded => "DejaVu Sans Mono (embedded)",
+        }
+    }
+
+    /// Returns the path of the file that this face is read from, or [`None`] if it is compiled into the binary.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Installed(font) => Some(&font.path),
+            Self::Embedded => None,
+        }
+    }
+
+    /// Returns the index of this face within the font file that contains it, which may be a collection of faces.
+    pub fn index(&self) -> usize {
+        match self {
+            Self::Installed(font) => font.index.map_or(0, |index| index as usize),
+            Self::Embedded => 0,
+        }
+    }
+
+    /// Returns the data of the font file that contains this face.
+    pub fn data(&self) -> std::io::Result<Cow<'static, [u8]>> {
+        match self {
+            Self::Installed(font) => std::fs::read(&font.path).map(Cow::Owned),
+            Self::Embedded => Ok(Cow::Borrowed(EMBEDDED_DATA)),
+        }
+    }
+}
@@ -0,0 +1,179 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Grids of dark and light modules, such as QR codes and barcodes.
+//!
+//! Codes are only scannable while every module keeps its exact shape, so rather than resampling the image, each pixel
+//! is thresholded and the size of a module is found from the lengths of the runs of matching pixels. Each module is
+//! then read from its center, which tolerates the slight blurring of codes that were scaled before being saved.
+
+use std::collections::BTreeSet;
+
+use image::{DynamicImage, GenericImageView, LumaA, Pixel};
+
+/// A grid of modules, each of which is either dark or light.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModuleGrid {
+    width: u32,
+    height: u32,
+    dark: Box<[bool]>,
+}
+
+impl ModuleGrid {
+    /// Reads the modules of the given image, treating pixels darker than the threshold as dark and pixels at or below
+    /// the alpha threshold as light.
+    pub fn from_image(image: &DynamicImage, threshold: u8, alpha_threshold: u8) -> Self {
+        let (image_width, image_height) = image.dimensions();
+        let pixels: Box<[bool]> = image
+            .pixels()
+            .map(|(.., pixel)| {
+                let LumaA([luma, alpha]) = pixel.to_luma_alpha();
+
+                alpha > alpha_threshold && luma < threshold
+            })
+            .collect();
+        let is_dark = |x: u32, y: u32| pixels[y as usize * image_width as usize + x as usize];
+
+        let mut run_lengths = BTreeSet::new();
+
+        for y in 0 .. image_height {
+            self::run_lengths((0 .. image_width).map(|x| is_dark(x, y)), &mut run_lengths);
+        }
+        for x in 0 .. image_width {
+            self::run_lengths((0 .. image_height).map(|y| is_dark(x, y)), &mut run_lengths);
+        }
+
+        let module_size = self::module_size(&run_lengths);
+        let (width, height) = (image_width / module_size, image_height / module_size);
+        let dark = (0 .. height)
+            .flat_map(|y| (0 .. width).map(move |x| (x, y)))
+            .map(|(x, y)| is_dark(x * module_size + module_size / 2, y * module_size + module_size / 2))
+            .collect();
+
+        Self { width, height, dark }
+    }
+
+    /// Returns the number of columns and rows of modules.
+    pub const fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Returns whether the module at the given column and row is dark.
+    pub fn is_dark(&self, x: u32, y: u32) -> bool {
+        self.dark[y as usize * self.width as usize + x as usize]
+    }
+}
+
+/// Adds the length of each run of matching values to the given set of lengths.
+fn run_lengths(values: impl Iterator<Item = bool>, lengths: &mut BTreeSet<u32>) {
+    let mut run: Option<(bool, u32)> = None;
+
+    for value in values {
+        run = match run {
+            Some((previous, length)) if previous == value => Some((previous, length + 1)),
+            Some((_, length)) => {
+                lengths.insert(length);
+
+                Some((value, 1))
+            }
+            None => Some((value, 1)),
+        };
+    }
+
+    lengths.extend(run.map(|(_, length)| length));
+}
+
+/// Returns the size of a module in pixels, given the lengths of every run of matching pixels.
+///
+/// Every run spans a whole number of modules, so the module size divides all of their lengths. Codes that were scaled
+/// by a fractional factor have runs a pixel longer or shorter than the rest, in which case the largest size that every
+/// length is within a quarter of a module of a multiple of is used instead.
+fn module_size(lengths: &BTreeSet<u32>) -> u32 {
+    let divisor = lengths.iter().fold(0, |divisor, length| self::gcd(divisor, *length));
+
+    if divisor > 1 {
+        return divisor;
+    }
+
+    // A module is never more than a quarter longer than the shortest run, which bounds the sizes that are tried.
+    let shortest = lengths.first().copied().unwrap_or(1);
+
+    (1 ..= shortest + shortest / 3 + 1)
+        .rev()
+        .find(|size| {
+            lengths.iter().all(|length| {
+                let modules = ((length + size / 2) / size).max(1);
+
+                (modules * size).abs_diff(*length) <= size / 4
+            })
+        })
+        .unwrap_or(1)
+}
+
+const fn gcd(mut lhs: u32, mut rhs: u32) -> u32 {
+    while rhs != 0 {
+        (lhs, rhs) = (rhs, lhs % rhs);
+    }
+
+    lhs
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, GrayImage, Luma};
+
+    use super::ModuleGrid;
+
+    const MODULES: [&str; 5] = ["#.##.", ".#..#", "##.#.", "..##.", "#...#"];
+
+    /// Returns an image of the modules, each drawn as a square of the given number of pixels.
+    fn image(module_size: u32) -> GrayImage {
+        GrayImage::from_fn(5 * module_size, 5 * module_size, |x, y| {
+            let dark = MODULES[(y / module_size) as usize].as_bytes()[(x / module_size) as usize] == b'#';
+
+            Luma([if dark { 0 } else { u8::MAX }])
+        })
+    }
+
+    fn assert_modules(grid: &ModuleGrid) {
+        assert_eq!(grid.size(), (5, 5));
+
+        for (y, row) in MODULES.iter().enumerate() {
+            for (x, module) in row.bytes().enumerate() {
+                assert_eq!(grid.is_dark(x as u32, y as u32), module == b'#', "the module at ({x}, {y}) differs");
+            }
+        }
+    }
+
+    #[test]
+    fn single_pixel_modules_are_read() {
+        self::assert_modules(&ModuleGrid::from_image(&DynamicImage::ImageLuma8(self::image(1)), 128, 0));
+    }
+
+    #[test]
+    fn scaled_modules_are_read() {
+        self::assert_modules(&ModuleGrid::from_image(&DynamicImage::ImageLuma8(self::image(4)), 128, 0));
+    }
+
+    #[test]
+    fn noisy_runs_keep_the_module_size() {
+        let mut image = self::image(4);
+
+        // The first module is drawn a pixel too wide, as though its edge was blurred while scaling, shortening the
+        // light module beside it.
+        for y in 0 .. 4 {
+            image.put_pixel(4, y, Luma([0]));
+        }
+
+        self::assert_modules(&ModuleGrid::from_image(&DynamicImage::ImageLuma8(image), 128, 0));
+    }
+}
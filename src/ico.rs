@@ -0,0 +1,96 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Resolution selection for ICO files.
+//!
+//! An ICO file contains a directory of images at different resolutions, of which the `image` crate always decodes the
+//! largest. The functions within this module instead select the entry that best fits the size at which the image will
+//! be drawn, then decode only that entry.
+
+use image::{DynamicImage, ImageFormat};
+
+use crate::error::{Error, Result};
+
+const HEADER_LENGTH: usize = 6;
+const ENTRY_LENGTH: usize = 16;
+
+/// A single image within an ICO file's directory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Entry {
+    index: usize,
+    width: u32,
+    height: u32,
+    bits_per_pixel: u16,
+    data_offset: usize,
+    data_length: usize,
+}
+
+/// Decodes the entry of the given ICO file that best fits within the target dimensions.
+///
+/// The smallest entry that covers the target dimensions is preferred, so that the image is only ever downscaled. If no
+/// entry is large enough, the largest entry is used instead.
+pub fn open_closest(data: &[u8], (target_width, target_height): (u32, u32)) -> Result<DynamicImage> {
+    let entries = self::entries(data)?;
+    let covers = |entry: &&Entry| entry.width >= target_width && entry.height >= target_height;
+    let area = |entry: &&Entry| (entry.width * entry.height, entry.bits_per_pixel);
+
+    let entry = match entries.iter().filter(covers).min_by_key(area) {
+        Some(entry) => entry,
+        None => entries.iter().max_by_key(area).ok_or_else(|| Error::decode("icon does not contain any images"))?,
+    };
+
+    let image_data = data
+        .get(entry.data_offset .. entry.data_offset + entry.data_length)
+        .ok_or_else(|| Error::decode("icon image data is out of bounds"))?;
+
+    // Rebuild the file with only the chosen entry, so that the decoder has no choice but to use it.
+    let mut icon = Vec::with_capacity(HEADER_LENGTH + ENTRY_LENGTH + image_data.len());
+
+    icon.extend_from_slice(&data[.. 4]);
+    icon.extend_from_slice(&1_u16.to_le_bytes());
+    icon.extend_from_slice(&data[HEADER_LENGTH + entry.index * ENTRY_LENGTH ..][.. ENTRY_LENGTH - 4]);
+    icon.extend_from_slice(&((HEADER_LENGTH + ENTRY_LENGTH) as u32).to_le_bytes());
+    icon.extend_from_slice(image_data);
+
+    image::load_from_memory_with_format(&icon, ImageFormat::Ico).map_err(Into::into)
+}
+
+fn entries(data: &[u8]) -> Result<Box<[Entry]>> {
+    if data.len() < HEADER_LENGTH {
+        return Err(Error::decode("icon header is truncated"));
+    }
+
+    let count = u16::from_le_bytes([data[4], data[5]]) as usize;
+
+    if data.len() < HEADER_LENGTH + count * ENTRY_LENGTH {
+        return Err(Error::decode("icon directory is truncated"));
+    }
+
+    let entries = data[HEADER_LENGTH ..].chunks_exact(ENTRY_LENGTH).take(count).enumerate().map(|(index, entry)| {
+        let u16_at = |offset: usize| u16::from_le_bytes([entry[offset], entry[offset + 1]]);
+        let u32_at = |offset: usize| {
+            u32::from_le_bytes([entry[offset], entry[offset + 1], entry[offset + 2], entry[offset + 3]])
+        };
+
+        // A stored dimension of zero represents 256 pixels.
+        Entry {
+            index,
+            width: if entry[0] == 0 { 256 } else { entry[0] as u32 },
+            height: if entry[1] == 0 { 256 } else { entry[1] as u32 },
+            bits_per_pixel: u16_at(6),
+            data_offset: u32_at(12) as usize,
+            data_length: u32_at(8) as usize,
+        }
+    });
+
+    Ok(entries.collect())
+}
@@ -0,0 +1,666 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! The brightness of each character, measured from the glyphs of a font face.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::fs::File;
+use std::io::BufReader;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use fontconfig::{Font, Fontconfig, ObjectSet, Pattern};
+use image::{LumaA, Pixel, Rgba};
+use rayon::ThreadPoolBuilder;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde::Deserialize;
+use swash::FontRef;
+use swash::scale::image::Content;
+use swash::scale::{Render, ScaleContext, Source, StrikeWith};
+use swash::zeno::Format;
+use unicode_width::UnicodeWidthChar;
+
+use crate::cache::{BrightnessCache, FileCache, GlyphMeasurement};
+use crate::cli::MeasureArguments;
+use crate::error::Error;
+use crate::font::FontFace;
+use crate::{DIRECTORIES, error};
+
+const CHARACTER_RANGE: (char, char) = ('\u{20}', '\u{7F}');
+
+pub const MAX_BRIGHTNESS: u16 = u8::MAX as u16 * u8::MAX as u16;
+
+const EMBEDDED_FONT_FAMILY: &str = "embedded";
+
+pub static FONT_CONFIG: LazyLock<Option<Fontconfig>> = LazyLock::new(|| Fontconfig::new().filter(self::has_fonts));
+
+static SCALE_CONTEXT: LazyLock<Mutex<ScaleContext>> = LazyLock::new(|| Mutex::new(ScaleContext::new()));
+
+/// A built-in set of characters that may be used to draw an image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    /// Every printable ASCII character.
+    #[default]
+    Ascii,
+    /// The Unicode shade and full block elements.
+    Blocks,
+    /// A short ramp of ten characters.
+    Simple,
+    /// The digits zero and one.
+    Binary,
+}
+
+impl Preset {
+    pub fn characters(self) -> Box<[char]> {
+        match self {
+            Self::Ascii => (CHARACTER_RANGE.0 ..= CHARACTER_RANGE.1).collect(),
+            Self::Blocks => Box::new(['░', '▒', '▓', '█']),
+            Self::Simple => " .:-=+*#%@".chars().collect(),
+            Self::Binary => Box::new(['0', '1']),
+        }
+    }
+}
+
+/// The slant of a font face.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Slant {
+    /// Upright glyphs.
+    #[default]
+    Roman,
+    /// Slanted glyphs, using an oblique face for fonts that have no italic face.
+    Italic,
+}
+
+impl Slant {
+    pub const fn fontconfig_slant(self) -> i32 {
+        match self {
+            Self::Roman => fontconfig::FC_SLANT_ROMAN,
+            Self::Italic => fontconfig::FC_SLANT_ITALIC,
+        }
+    }
+}
+
+/// The hinting applied to glyph outlines during rasterization.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Hinting {
+    /// Outlines are rasterized exactly as designed.
+    #[default]
+    None,
+    /// Outlines are fitted to the pixel grid.
+    Full,
+}
+
+/// The settings used when rasterizing glyphs to measure their brightness.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RasterSettings {
+    pub size: Option<f32>,
+    pub scale: f32,
+    pub slant: Slant,
+    pub hinting: Hinting,
+    pub subpixel: bool,
+}
+
+/// How glyphs are distributed across threads while they are measured.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Parallelism {
+    /// Every glyph is measured on the current thread.
+    Sequential,
+    /// Glyphs are measured within a pool of the given number of threads, or one for each core.
+    Parallel(Option<NonZeroUsize>),
+}
+
+/// A set of characters that may be used to draw an image.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Charset {
+    pub characters: Box<[char]>,
+}
+
+impl Charset {
+    pub fn from_preset(preset: Preset) -> Self {
+        Self { characters: preset.characters() }
+    }
+
+    pub fn from_custom(charset: &str) -> Self {
+        let mut characters: Vec<char> = charset.chars().collect();
+
+        characters.sort_unstable();
+        characters.dedup();
+
+        Self { characters: characters.into() }
+    }
+}
+
+/// An inclusive range of brightnesses that are drawn using a specific character.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub struct Threshold {
+    pub start: u16,
+    pub end: u16,
+    pub character: char,
+}
+
+/// The mapping used to determine which character represents a given brightness.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CharacterMap {
+    /// Selects the character whose measured brightness is closest.
+    Nearest(HashMap<char, u16>),
+    /// Selects the character whose range contains the brightness.
+    Thresholds(Box<[Threshold]>),
+}
+
+impl CharacterMap {
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Self::Nearest(brightnesses) => brightnesses.is_empty(),
+            Self::Thresholds(thresholds) => thresholds.is_empty(),
+        }
+    }
+
+    /// Returns this map reduced to the characters nearest to the given number of evenly spaced brightnesses.
+    pub fn smoothed(self, levels: u16) -> Self {
+        let Self::Nearest(ref brightnesses) = self else {
+            return self;
+        };
+
+        let smoothed = (0 .. levels)
+            .filter_map(|level| self.character((MAX_BRIGHTNESS as u32 * level as u32 / (levels - 1) as u32) as u16))
+            .map(|character| (character, brightnesses[&character]))
+            .collect();
+
+        Self::Nearest(smoothed)
+    }
+
+    /// Returns each character of this map and its brightness, from darkest to brightest.
+    pub fn ramp(&self) -> Vec<(char, u16)> {
+        let mut ramp: Vec<(char, u16)> = match self {
+            Self::Nearest(brightnesses) => brightnesses.iter().map(|(c, b)| (*c, *b)).collect(),
+            Self::Thresholds(thresholds) => {
+                thresholds.iter().map(|threshold| (threshold.character, threshold.start)).collect()
+            }
+        };
+
+        ramp.sort_unstable_by_key(|(c, b)| (*b, *c));
+        ramp
+    }
+
+    pub fn character(&self, brightness: u16) -> Option<char> {
+        match self {
+            // Ties are broken by preferring the lowest code point, since the map's iteration order is unspecified.
+            Self::Nearest(brightnesses) => brightnesses
+                .iter()
+                .map(|(c, b)| (*c, b.abs_diff(brightness)))
+                .min_by_key(|(c, b)| (*b, *c))
+                .map(|(c, _)| c),
+            Self::Thresholds(thresholds) => thresholds
+                .iter()
+                .find(|threshold| (threshold.start ..= threshold.end).contains(&brightness))
+                .map(|threshold| threshold.character),
+        }
+    }
+}
+
+pub fn measure_settings(arguments: &MeasureArguments) -> (Box<[FontFace]>, Charset, RasterSettings) {
+    let charset =
+        arguments.charset.as_deref().map_or_else(|| Charset::from_preset(arguments.preset), Charset::from_custom);
+    let fonts = if arguments.font.is_empty() {
+        Box::from([self::resolve_font("", arguments.slant)])
+    } else {
+        arguments.font.iter().map(|font_family| self::resolve_font(font_family, arguments.slant)).collect()
+    };
+    let raster_settings = RasterSettings {
+        size: arguments.glyph_size,
+        scale: arguments.glyph_scale,
+        slant: arguments.slant,
+        hinting: arguments.hinting,
+        subpixel: arguments.subpixel,
+    };
+
+    (fonts, charset, raster_settings)
+}
+
+pub fn load_thresholds(path: &Path) -> Result<Box<[Threshold]>> {
+    let mut thresholds: Vec<Threshold> = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+
+    thresholds.sort_unstable_by_key(|threshold| threshold.start);
+
+    let mut next_start = Some(0);
+
+    for Threshold { start, end, character } in thresholds.iter().copied() {
+        anyhow::ensure!(start <= end, "threshold {start}..={end} is reversed");
+        anyhow::ensure!(character.width() == Some(1), "threshold character '{character}' is not one column wide");
+
+        let Some(expected_start) = next_start else {
+            anyhow::bail!("threshold {start}..={end} overlaps a previous range");
+        };
+
+        anyhow::ensure!(start >= expected_start, "threshold {start}..={end} overlaps a previous range");
+        anyhow::ensure!(start == expected_start, "thresholds do not cover brightnesses {expected_start}..{start}");
+
+        next_start = end.checked_add(1);
+    }
+
+    if let Some(start) = next_start {
+        anyhow::ensure!(start > MAX_BRIGHTNESS, "thresholds do not cover brightnesses {start}..={MAX_BRIGHTNESS}");
+    }
+
+    Ok(thresholds.into_boxed_slice())
+}
+
+fn resolve_font(font_family: &str, slant: Slant) -> FontFace {
+    if font_family != EMBEDDED_FONT_FAMILY {
+        if let Some(font) = self::match_font(font_family, slant).or_else(|| self::match_font("", slant)) {
+            return FontFace::Installed(font);
+        }
+
+        eprintln!("warning: no fonts are installed, so the glyphs of the embedded font will be measured");
+    }
+
+    if slant != Slant::Roman {
+        eprintln!("warning: the embedded font has no slanted face, so its upright glyphs will be measured");
+    }
+
+    FontFace::Embedded
+}
+
+fn has_fonts(_: &Fontconfig) -> bool {
+    // The wrappers of fontconfig dereference the null results returned when no fonts are installed, so the installed
+    // fonts are counted directly before any of them are used.
+    let fonts = unsafe { fontconfig_sys::FcConfigGetFonts(std::ptr::null_mut(), fontconfig_sys::FcSetSystem) };
+
+    !fonts.is_null() && unsafe { (*fonts).nfont } > 0
+}
+
+fn match_font(font_family: &str, slant: Slant) -> Option<Font> {
+    let mut pattern = Pattern::new(FONT_CONFIG.as_ref()?);
+
+    pattern.add_string(fontconfig::FC_FAMILY, &CString::new(font_family).ok()?);
+    pattern.add_integer(fontconfig::FC_SLANT, slant.fontconfig_slant());
+
+    let font_match = pattern.font_match();
+    let font = Font {
+        name: font_match.name()?.to_owned(),
+        path: font_match.filename()?.into(),
+        index: font_match.face_index(),
+    };
+
+    // Fontconfig matches the closest face, which is upright for fonts that have no slanted faces at all. The match is
+    // then reported as slanted when the face is meant to be slanted synthetically, so the face itself is looked up.
+    if slant != Slant::Roman && self::face_slant(&font) == Some(fontconfig::FC_SLANT_ROMAN) {
+        eprintln!("warning: font '{}' has no slanted face, so its upright glyphs will be measured", font.name);
+    }
+
+    Some(font)
+}
+
+fn face_slant(font: &Font) -> Option<i32> {
+    let font_config = FONT_CONFIG.as_ref()?;
+    let mut pattern = Pattern::new(font_config);
+    let mut objects = ObjectSet::new(font_config);
+
+    pattern.add_string(fontconfig::FC_FILE, &CString::new(font.path.to_str()?).ok()?);
+    pattern.add_integer(fontconfig::FC_INDEX, font.index.unwrap_or(0));
+    objects.add(fontconfig::FC_SLANT);
+
+    fontconfig::list_fonts(&pattern, Some(&objects)).iter().find_map(|pattern| pattern.slant())
+}
+
+pub fn font_ref<'d>(font: &FontFace, font_data: &'d [u8]) -> error::Result<FontRef<'d>> {
+    FontRef::from_index(font_data, font.index())
+        .ok_or_else(|| Error::font(format!("font '{}' is invalid", font.name())))
+}
+
+pub fn glyph_cache() -> FileCache {
+    FileCache::new(DIRECTORIES.cache_dir().join("glyphs"))
+}
+
+fn is_block_element(character: char) -> bool {
+    matches!(character, '\u{2580}' ..= '\u{259F}')
+}
+
+fn cell_area(font_ref: FontRef<'_>, characters: impl Iterator<Item = char>, raster_settings: RasterSettings) -> u64 {
+    let (mut metrics, mut glyph_metrics) = (font_ref.metrics(&[]), font_ref.glyph_metrics(&[]));
+
+    // Glyphs are rasterized in font units when no size is given, so the metrics are only scaled alongside them.
+    if let Some(size) = raster_settings.size {
+        metrics = metrics.scale(size * raster_settings.scale);
+        glyph_metrics = glyph_metrics.scale(size * raster_settings.scale);
+    }
+
+    let width =
+        characters.map(|character| glyph_metrics.advance_width(font_ref.charmap().map(character))).fold(0.0, f32::max);
+
+    width.ceil() as u64 * (metrics.ascent + metrics.descent).ceil() as u64
+}
+
+pub fn brightness_cache_key(font: &FontFace, raster_settings: RasterSettings) -> String {
+    let mut key = font.name().to_owned();
+
+    if let Some(size) = raster_settings.size {
+        key.push_str(&format!("@{size}px"));
+    }
+    if raster_settings.scale != 1.0 {
+        key.push_str(&format!("@{}x", raster_settings.scale));
+    }
+    if raster_settings.slant == Slant::Italic {
+        key.push_str("+italic");
+    }
+    if raster_settings.hinting == Hinting::Full {
+        key.push_str("+hinted");
+    }
+    if raster_settings.subpixel {
+        key.push_str("+subpixel");
+    }
+
+    key
+}
+
+pub fn compute_brightnesses(
+    cache: &mut impl BrightnessCache,
+    fonts: &[FontFace],
+    charset: &Charset,
+    raster_settings: RasterSettings,
+    keep_unaligned: bool,
+    normalize_percentile: f32,
+    parallelism: Parallelism,
+) -> error::Result<HashMap<char, u16>> {
+    let drawable_characters: Vec<char> = charset
+        .characters
+        .iter()
+        .copied()
+        .filter(|character| !character.is_whitespace() && !character.is_control())
+        .collect();
+    // Each font's measurements are cached separately, so that they are reused by any list of fonts that includes it.
+    let mut measurements: HashMap<char, GlyphMeasurement> = HashMap::new();
+    let mut present_characters = HashSet::new();
+    let mut block_cell_area = 0;
+
+    for font in fonts {
+        let cache_key = self::brightness_cache_key(font, raster_settings);
+        let mut font_measurements = cache.get(&cache_key)?.unwrap_or_default();
+
+        let font_data = font.data()?;
+        let font_ref = self::font_ref(font, &font_data)?;
+
+        // The terminal draws each character in the first font that contains it, so later fonts only fill the gaps.
+        let characters: Vec<char> = drawable_characters
+            .iter()
+            .copied()
+            .filter(|character| !present_characters.contains(character) && font_ref.charmap().map(*character) != 0)
+            .collect();
+
+        present_characters.extend(characters.iter().copied());
+
+        let characters: Vec<char> = characters
+            .into_iter()
+            .filter(|character| {
+                // Glyphs without an advance, such as combining marks, are drawn over their neighbors by the terminal,
+                // and wide glyphs, such as CJK characters and emoji, occupy two columns.
+                let advance_width = font_ref.glyph_metrics(&[]).advance_width(font_ref.charmap().map(*character));
+
+                keep_unaligned || (advance_width > 0.0 && character.width() == Some(1))
+            })
+            .collect();
+        let missing_characters: Vec<char> =
+            characters.iter().copied().filter(|character| !font_measurements.contains_key(character)).collect();
+
+        if !missing_characters.is_empty() {
+            font_measurements.extend(self::measure_glyphs(
+                font_ref,
+                &missing_characters,
+                raster_settings,
+                parallelism,
+            )?);
+
+            cache.put(&cache_key, &font_measurements)?;
+        }
+
+        // Block elements are designed to fill the terminal's cell exactly, so their coverage is only accurate relative
+        // to the whole cell, rather than to the largest glyph, which for most ramps is smaller than the cell.
+        if characters.iter().copied().any(self::is_block_element) {
+            block_cell_area =
+                block_cell_area.max(self::cell_area(font_ref, characters.iter().copied(), raster_settings));
+        }
+
+        measurements.extend(
+            characters
+                .into_iter()
+                .filter_map(|character| font_measurements.get(&character).map(|measurement| (character, *measurement))),
+        );
+    }
+
+    for character in drawable_characters.iter().filter(|character| !present_characters.contains(character)) {
+        match fonts {
+            [font] => eprintln!("warning: font '{}' does not contain the character '{character}'", font.name()),
+            _ => eprintln!("warning: none of the fonts contain the character '{character}'"),
+        }
+    }
+
+    let maximum_width = measurements.values().map(|measurement| measurement.width).max().unwrap_or(0);
+    let maximum_height = measurements.values().map(|measurement| measurement.height).max().unwrap_or(0);
+    let pixels_per_cell = (maximum_width as u64 * maximum_height as u64).max(block_cell_area);
+    // Whitespace is never measured, but a space draws nothing and so exactly represents a brightness of zero.
+    let blank = charset.characters.contains(&' ').then_some((' ', 0));
+
+    // Nothing may remain to be measured once unaligned and missing characters are removed, even if a cell was.
+    if pixels_per_cell == 0 || measurements.is_empty() {
+        return Ok(blank.into_iter().collect());
+    }
+
+    let mut brightnesses: HashMap<char, u16> = measurements
+        .into_iter()
+        .map(|(character, measurement)| (character, (measurement.coverage / pixels_per_cell) as u16))
+        .collect();
+    let mut sorted_brightnesses: Vec<u16> = brightnesses.values().copied().collect();
+
+    sorted_brightnesses.sort_unstable();
+
+    // Glyphs brighter than the reference brightness are clamped, since they cannot be brighter than fully bright.
+    let reference_index = ((sorted_brightnesses.len() - 1) as f32 * normalize_percentile / 100.0).round() as usize;
+    let brightness_scale = sorted_brightnesses[reference_index].max(1) as f64 / MAX_BRIGHTNESS as f64;
+
+    brightnesses.values_mut().for_each(|value| {
+        *value = ((*value) as f64 / brightness_scale).min(MAX_BRIGHTNESS as f64) as u16;
+    });
+    brightnesses.extend(blank);
+
+    Ok(brightnesses)
+}
+
+fn measure_glyphs(
+    font_ref: FontRef<'_>,
+    characters: &[char],
+    raster_settings: RasterSettings,
+    parallelism: Parallelism,
+) -> error::Result<HashMap<char, GlyphMeasurement>> {
+    let mut render = Render::new(&[Source::ColorOutline(0), Source::ColorBitmap(StrikeWith::BestFit), Source::Outline]);
+
+    render.default_color([0xFF; 4]);
+
+    if raster_settings.subpixel {
+        render.format(Format::Subpixel);
+    }
+
+    let measure = |&character: &char| {
+        let mut context = SCALE_CONTEXT.lock().unwrap();
+        let glyph_scaler_builder = context.builder(font_ref).hint(raster_settings.hinting == Hinting::Full);
+        let mut glyph_scaler = match raster_settings.size {
+            Some(size) => glyph_scaler_builder.size(size * raster_settings.scale).build(),
+            None => glyph_scaler_builder.build(),
+        };
+
+        let image = render.render(&mut glyph_scaler, font_ref.charmap().map(character))?;
+
+        drop(context);
+
+        // Each subpixel is scaled to the same range as the coverage of a whole pixel, which is its luma times its
+        // alpha.
+        let subpixel_coverage = (image.content == Content::SubpixelMask).then(|| {
+            image.data.array_chunks::<4>().fold([0_u64; 3], |coverage, pixel| {
+                std::array::from_fn(|channel| coverage[channel] + pixel[channel] as u64 * u8::MAX as u64)
+            })
+        });
+        let coverage = match subpixel_coverage {
+            Some(subpixel_coverage) => subpixel_coverage.iter().sum::<u64>() / 3,
+            None => image
+                .data
+                .array_chunks::<4>()
+                .copied()
+                .map(|pixel| Rgba(pixel).to_luma_alpha())
+                .fold(0, |coverage, LumaA([luma, alpha])| coverage + (luma as u64 * alpha as u64)),
+        };
+
+        Some((character, GlyphMeasurement {
+            width: image.placement.width,
+            height: image.placement.height,
+            coverage,
+            subpixel_coverage,
+        }))
+    };
+
+    match parallelism {
+        Parallelism::Sequential => Ok(characters.iter().filter_map(measure).collect()),
+        Parallelism::Parallel(threads) => {
+            // The pool is only built when glyphs must actually be measured, since drawing never needs it.
+            let pool = ThreadPoolBuilder::new().num_threads(threads.map_or(0, NonZeroUsize::get)).build()?;
+
+            Ok(pool.install(|| characters.par_iter().filter_map(measure).collect()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{CharacterMap, Charset, Hinting, Parallelism, RasterSettings, Slant, Threshold};
+    use crate::cache::{BrightnessCache, GlyphMeasurement};
+    use crate::error::Result;
+    use crate::font::FontFace;
+
+    /// A cache that keeps its measurements in memory and records every set of measurements that is stored.
+    #[derive(Default)]
+    struct MemoryCache {
+        entries: HashMap<String, HashMap<char, GlyphMeasurement>>,
+        writes: Vec<HashMap<char, GlyphMeasurement>>,
+    }
+
+    impl BrightnessCache for MemoryCache {
+        fn get(&self, key: &str) -> Result<Option<HashMap<char, GlyphMeasurement>>> {
+            Ok(self.entries.get(key).cloned())
+        }
+
+        fn put(&mut self, key: &str, measurements: &HashMap<char, GlyphMeasurement>) -> Result<()> {
+            self.entries.insert(key.to_owned(), measurements.clone());
+            self.writes.push(measurements.clone());
+
+            Ok(())
+        }
+    }
+
+    /// Measures the given characters in the embedded font, reusing and updating the measurements in the cache.
+    fn measure(cache: &mut MemoryCache, characters: &str) -> HashMap<char, u16> {
+        let raster_settings =
+            RasterSettings { size: None, scale: 1.0, slant: Slant::default(), hinting: Hinting::None, subpixel: false };
+        let charset = Charset { characters: characters.chars().collect() };
+
+        super::compute_brightnesses(
+            cache,
+            &[FontFace::Embedded],
+            &charset,
+            raster_settings,
+            false,
+            100.0,
+            Parallelism::Sequential,
+        )
+        .expect("the glyphs should be measured")
+    }
+
+    /// Loads thresholds from a temporary file with the given contents.
+    fn load_thresholds(name: &str, contents: &str) -> anyhow::Result<Box<[Threshold]>> {
+        let path = std::env::temp_dir().join(format!("term-render-{}-{name}.json", std::process::id()));
+
+        std::fs::write(&path, contents).expect("the thresholds should be written");
+
+        let thresholds = super::load_thresholds(&path);
+
+        std::fs::remove_file(&path).expect("the thresholds should be removed");
+
+        thresholds
+    }
+
+    #[test]
+    fn thresholds_are_loaded_in_order() {
+        let thresholds = self::load_thresholds(
+            "valid",
+            r#"[{ "start": 30000, "end": 65025, "character": "@" }, { "start": 0, "end": 29999, "character": " " }]"#,
+        )
+        .expect("the thresholds should be loaded");
+
+        let dark = Threshold { start: 0, end: 29999, character: ' ' };
+        let bright = Threshold { start: 30000, end: 65025, character: '@' };
+
+        assert_eq!(&*thresholds, [dark, bright]);
+    }
+
+    #[test]
+    fn invalid_thresholds_are_rejected() {
+        let malformed = self::load_thresholds("malformed", r#"[{ "start": 0, "end": 65025, "character": "@" "#);
+        let out_of_range = self::load_thresholds("out-of-range", r#"[{ "start": 0, "end": 70000, "character": "@" }]"#);
+        let empty = self::load_thresholds("empty", "");
+
+        assert!(malformed.is_err(), "malformed thresholds were loaded as {malformed:?}");
+        assert!(out_of_range.is_err(), "out of range thresholds were loaded as {out_of_range:?}");
+        assert!(empty.is_err(), "an empty file was loaded as {empty:?}");
+    }
+
+    #[test]
+    fn brightness_ties_prefer_the_lowest_code_point() {
+        // Every map hashes its characters differently, so the tie would be broken by chance if it were left to the
+        // iteration order.
+        for _ in 0 .. 32 {
+            let map = CharacterMap::Nearest(HashMap::from([('x', 1000), ('+', 1000), ('o', 1000), ('@', 9000)]));
+
+            assert_eq!(map.character(0), Some('+'));
+            assert_eq!(map.character(1000), Some('+'));
+            assert_eq!(map.character(u16::MAX), Some('@'));
+            assert_eq!(map.ramp(), [('+', 1000), ('o', 1000), ('x', 1000), ('@', 9000)]);
+        }
+    }
+
+    #[test]
+    fn cached_glyphs_are_not_measured_again() {
+        let mut cache = MemoryCache::default();
+
+        self::measure(&mut cache, "#@");
+
+        // A measurement that rasterizing could never produce shows whether the cached glyph was reused.
+        let cached = GlyphMeasurement { width: 1, height: 1, coverage: 1, subpixel_coverage: None };
+
+        cache.entries.values_mut().for_each(|measurements| _ = measurements.insert('#', cached));
+
+        self::measure(&mut cache, "@#");
+
+        assert_eq!(cache.writes.len(), 1, "nothing should be stored when every glyph is cached");
+
+        self::measure(&mut cache, "#@%");
+
+        assert_eq!(cache.writes.len(), 2, "the new glyph should be stored");
+
+        let written = &cache.writes[1];
+
+        assert_eq!(written.get(&'#'), Some(&cached));
+        assert!(written.contains_key(&'%'), "the new glyph should be measured");
+    }
+}
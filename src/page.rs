@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Page selection for multi-page images.
+//!
+//! The `image` crate only ever decodes the first page of a TIFF file and the first frame of an animated GIF. The
+//! functions within this module instead use the format-specific decoders to skip to a given page, which are numbered
+//! starting from one.
+
+use std::io::Cursor;
+
+use image::codecs::gif::GifDecoder;
+use image::{AnimationDecoder, DynamicImage, ImageBuffer, ImageFormat};
+use tiff::ColorType;
+use tiff::decoder::{Decoder, DecodingResult};
+
+use crate::error::{Error, Result};
+
+/// Decodes the given page of an image with the given format.
+///
+/// Formats that do not support multiple pages are treated as containing a single page.
+pub fn open(data: &[u8], format: ImageFormat, page: usize) -> Result<DynamicImage> {
+    match format {
+        ImageFormat::Tiff => self::open_tiff(data, page),
+        ImageFormat::Gif => self::open_gif(data, page),
+        _ => {
+            if page != 1 {
+                return Err(Error::decode(format!("page {page} is out of range, as the image only contains 1 page")));
+            }
+
+            image::load_from_memory_with_format(data, format).map_err(Into::into)
+        }
+    }
+}
+
+fn open_tiff(data: &[u8], page: usize) -> Result<DynamicImage> {
+    let mut decoder = Decoder::new(Cursor::new(data))?;
+    let mut count = 1;
+
+    while count < page && decoder.more_images() {
+        decoder.next_image()?;
+
+        count += 1;
+    }
+
+    if count != page {
+        return Err(Error::decode(format!("page {page} is out of range, as the image only contains {count} pages")));
+    }
+
+    let (width, height) = decoder.dimensions()?;
+
+    let image = match (decoder.colortype()?, decoder.read_image()?) {
+        (ColorType::Gray(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma8)
+        }
+        (ColorType::Gray(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma16)
+        }
+        (ColorType::GrayA(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA8)
+        }
+        (ColorType::GrayA(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA16)
+        }
+        (ColorType::RGB(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb8)
+        }
+        (ColorType::RGB(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb16)
+        }
+        (ColorType::RGB(32), DecodingResult::F32(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb32F)
+        }
+        (ColorType::RGBA(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba8)
+        }
+        (ColorType::RGBA(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba16)
+        }
+        (ColorType::RGBA(32), DecodingResult::F32(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba32F)
+        }
+        (color_type, _) => {
+            return Err(Error::decode(format!("TIFF pages with the color type {color_type:?} are not supported")));
+        }
+    };
+
+    image.ok_or_else(|| Error::decode("TIFF page data is truncated"))
+}
+
+fn open_gif(data: &[u8], page: usize) -> Result<DynamicImage> {
+    let mut count = 0;
+
+    for frame in GifDecoder::new(Cursor::new(data))?.into_frames() {
+        let frame = frame?;
+
+        count += 1;
+
+        // Frames are composited onto the previous frames, so the earlier frames must still be decoded.
+        if count == page {
+            return Ok(DynamicImage::ImageRgba8(frame.into_buffer()));
+        }
+    }
+
+    Err(Error::decode(format!("page {page} is out of range, as the image only contains {count} pages")))
+}
@@ -0,0 +1,59 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Generation of a test pattern.
+//!
+//! The test pattern provides a known reference for calibrating how images are drawn. Its upper half contains a smooth
+//! and a stepped brightness ramp for tuning the charset, and its lower half contains a sweep of hues for tuning
+//! colors. A grid of square cells and a circle are drawn over the pattern, which only appear square and round when
+//! the aspect ratio of the terminal's cells is accounted for correctly.
+
+use image::{DynamicImage, Rgb, RgbImage};
+
+use crate::color;
+
+const WIDTH: u32 = 512;
+const HEIGHT: u32 = 256;
+const GRID_SPACING: u32 = 32;
+const BRIGHTNESS_STEPS: u32 = 16;
+
+/// Returns a newly generated test pattern.
+pub fn generate() -> DynamicImage {
+    let center = (WIDTH as f32 / 2.0, HEIGHT as f32 / 2.0);
+    let radius = HEIGHT as f32 * 0.45;
+
+    let image = RgbImage::from_fn(WIDTH, HEIGHT, |x, y| {
+        let distance = f32::hypot(x as f32 + 0.5 - center.0, y as f32 + 0.5 - center.1);
+
+        if x % GRID_SPACING == 0 || y % GRID_SPACING == 0 || (distance - radius).abs() < 1.5 {
+            return Rgb([u8::MAX; 3]);
+        }
+
+        let progress = x as f32 / (WIDTH - 1) as f32;
+
+        match y * 4 / HEIGHT {
+            0 => Rgb([(progress * u8::MAX as f32).round() as u8; 3]),
+            1 => {
+                let step = (x * BRIGHTNESS_STEPS / WIDTH) as f32 / (BRIGHTNESS_STEPS - 1) as f32;
+
+                Rgb([(step * u8::MAX as f32).round() as u8; 3])
+            }
+            row => {
+                let value = if row == 2 { 1.0 } else { 0.5 };
+
+                Rgb(color::hsv_to_rgb([progress * 360.0, 1.0, value]))
+            }
+        }
+    });
+
+    DynamicImage::ImageRgb8(image)
+}
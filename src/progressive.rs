@@ -0,0 +1,133 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Quick previews of progressive JPEG files.
+//!
+//! Progressive JPEG files begin with the scans that hold the average color of each 8x8 block of the image, and refine
+//! it with each later scan. Reading only those first scans gives a coarse preview of the whole image from a fraction
+//! of the file, which can be drawn while the rest of the file is read and decoded in full.
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use image::{DynamicImage, ImageFormat};
+
+const MARKER_START_OF_IMAGE: u8 = 0xD8;
+const MARKER_END_OF_IMAGE: u8 = 0xD9;
+const MARKER_START_OF_SCAN: u8 = 0xDA;
+const MARKER_PROGRESSIVE: u8 = 0xC2;
+
+/// Decodes a preview of the given file from its first scans, if it is a progressive JPEG file.
+///
+/// Previews are only a convenience, so any file that cannot be previewed, for whatever reason, returns [`None`] and
+/// is left to be decoded normally.
+pub fn preview(path: &Path) -> Option<DynamicImage> {
+    let mut data = self::first_scans(&mut BufReader::new(File::open(path).ok()?))?;
+
+    data.extend([0xFF, MARKER_END_OF_IMAGE]);
+
+    image::load_from_memory_with_format(&data, ImageFormat::Jpeg).ok()
+}
+
+fn first_scans(reader: &mut impl Read) -> Option<Vec<u8>> {
+    let mut data = vec![self::byte(reader)?, self::byte(reader)?];
+
+    if data != [0xFF, MARKER_START_OF_IMAGE] {
+        return None;
+    }
+
+    let mut progressive = false;
+    let mut marker = self::marker(reader)?;
+
+    loop {
+        // The length of a segment includes its own two bytes.
+        let length = u16::from_be_bytes([self::byte(reader)?, self::byte(reader)?]);
+        let mut segment = vec![0; (length as usize).checked_sub(2)?];
+
+        reader.read_exact(&mut segment).ok()?;
+
+        match marker {
+            // Every other frame type is decoded just as quickly in full, so only progressive files are previewed.
+            0xC0 ..= 0xCF if ![0xC4, 0xC8, 0xCC].contains(&marker) => {
+                if marker != MARKER_PROGRESSIVE {
+                    return None;
+                }
+
+                progressive = true;
+            }
+            MARKER_START_OF_SCAN => {
+                let component_count = *segment.first()? as usize;
+                let spectral_start = *segment.get(1 + component_count * 2)?;
+
+                // Scans that begin beyond the first coefficient only refine the detail within each block.
+                if !progressive || spectral_start > 0 {
+                    break;
+                }
+            }
+            _ => {}
+        }
+
+        data.extend([0xFF, marker]);
+        data.extend(length.to_be_bytes());
+        data.extend(segment);
+
+        marker =
+            if marker == MARKER_START_OF_SCAN { self::scan_data(reader, &mut data)? } else { self::marker(reader)? };
+
+        if marker == MARKER_END_OF_IMAGE {
+            return None;
+        }
+    }
+
+    progressive.then_some(data)
+}
+
+/// Copies a scan's entropy-coded data into `data`, returning the marker that ends it.
+fn scan_data(reader: &mut impl Read, data: &mut Vec<u8>) -> Option<u8> {
+    loop {
+        let byte = self::byte(reader)?;
+
+        if byte != 0xFF {
+            data.push(byte);
+
+            continue;
+        }
+
+        match self::byte(reader)? {
+            // A zero byte escapes a literal `0xFF`, and restart markers may appear within the data itself.
+            next @ (0x00 | 0xD0 ..= 0xD7) => data.extend([byte, next]),
+            marker => return Some(marker),
+        }
+    }
+}
+
+fn marker(reader: &mut impl Read) -> Option<u8> {
+    if self::byte(reader)? != 0xFF {
+        return None;
+    }
+
+    // Any number of fill bytes may precede a marker.
+    let mut marker = self::byte(reader)?;
+
+    while marker == 0xFF {
+        marker = self::byte(reader)?;
+    }
+
+    Some(marker)
+}
+
+fn byte(reader: &mut impl Read) -> Option<u8> {
+    let mut byte = 0;
+
+    reader.read_exact(std::slice::from_mut(&mut byte)).ok().map(|()| byte)
+}
@@ -0,0 +1,294 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Decoding of RAW camera images stored as uncompressed DNG files.
+//!
+//! Only DNG files whose color filter array data is uncompressed, stored in strips and 8 or 16 bits per sample are
+//! supported. Other RAW formats, such as CR2, NEF and ARW, and most DNG files written by cameras store their data
+//! compressed, tiled or packed into 12 or 14 bits per sample, and are reported as unsupported rather than decoded
+//! incorrectly. The sensor data is demosaiced by combining each 2x2 block of the color filter array
+//! into a single pixel, then white balanced and tone mapped so that the result resembles the intended photograph.
+
+use std::collections::HashMap;
+
+use image::{DynamicImage, RgbImage};
+
+use crate::error::{Error, Result};
+
+const TAG_WIDTH: u16 = 256;
+const TAG_HEIGHT: u16 = 257;
+const TAG_BITS_PER_SAMPLE: u16 = 258;
+const TAG_COMPRESSION: u16 = 259;
+const TAG_PHOTOMETRIC_INTERPRETATION: u16 = 262;
+const TAG_STRIP_OFFSETS: u16 = 273;
+const TAG_STRIP_BYTE_COUNTS: u16 = 279;
+const TAG_TILE_OFFSETS: u16 = 324;
+const TAG_SUB_IFDS: u16 = 330;
+const TAG_CFA_PATTERN: u16 = 33422;
+const TAG_BLACK_LEVEL: u16 = 50714;
+const TAG_WHITE_LEVEL: u16 = 50717;
+const TAG_AS_SHOT_NEUTRAL: u16 = 50728;
+
+const PHOTOMETRIC_CFA: u16 = 32803;
+const COMPRESSION_NONE: u16 = 1;
+
+/// The file extensions of the RAW formats that may be decoded.
+pub const EXTENSIONS: [&str; 1] = ["dng"];
+
+/// A TIFF file's byte order and contents.
+#[derive(Clone, Copy, Debug)]
+struct Tiff<'d> {
+    data: &'d [u8],
+    little_endian: bool,
+}
+
+/// An image file directory, mapping each tag to its values.
+type Directory = HashMap<u16, Box<[f64]>>;
+
+impl Tiff<'_> {
+    fn u16_at(self, offset: usize) -> Result<u16> {
+        let bytes = self.data.get(offset .. offset + 2).ok_or_else(|| Error::decode("unexpected end of file"))?;
+        let bytes = [bytes[0], bytes[1]];
+
+        Ok(if self.little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    }
+
+    fn u32_at(self, offset: usize) -> Result<u32> {
+        let bytes = self.data.get(offset .. offset + 4).ok_or_else(|| Error::decode("unexpected end of file"))?;
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+
+        Ok(if self.little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    }
+
+    fn directory(self, offset: usize) -> Result<(Directory, usize)> {
+        let count = self.u16_at(offset)? as usize;
+        let mut directory = Directory::new();
+
+        for entry in (0 .. count).map(|index| offset + 2 + index * 12) {
+            let tag = self.u16_at(entry)?;
+            let field_type = self.u16_at(entry + 2)?;
+            let length = self.u32_at(entry + 4)? as usize;
+
+            let field_size = match field_type {
+                1 | 7 => 1,
+                3 => 2,
+                4 | 13 => 4,
+                5 => 8,
+                _ => continue,
+            };
+            // Values that fit within the entry are stored inline rather than at an offset.
+            let start = if field_size * length <= 4 { entry + 8 } else { self.u32_at(entry + 8)? as usize };
+
+            let values = (0 .. length)
+                .map(|index| {
+                    let position = start + index * field_size;
+
+                    Ok(match field_size {
+                        1 => *self.data.get(position).ok_or_else(|| Error::decode("unexpected end of file"))? as f64,
+                        2 => self.u16_at(position)? as f64,
+                        4 => self.u32_at(position)? as f64,
+                        _ => self.u32_at(position)? as f64 / self.u32_at(position + 4)?.max(1) as f64,
+                    })
+                })
+                .collect::<Result<_>>()?;
+
+            directory.insert(tag, values);
+        }
+
+        Ok((directory, self.u32_at(offset + 2 + count * 12)? as usize))
+    }
+
+    fn directories(self) -> Result<Vec<Directory>> {
+        let mut pending = vec![self.u32_at(4)? as usize];
+        let mut directories = Vec::new();
+
+        while let Some(offset) = pending.pop() {
+            // A zero offset ends a chain of directories, and limiting their number guards against cycles.
+            if offset == 0 || directories.len() > 64 {
+                continue;
+            }
+
+            let (directory, next_offset) = self.directory(offset)?;
+
+            pending.push(next_offset);
+            pending.extend(directory.get(&TAG_SUB_IFDS).into_iter().flatten().map(|offset| *offset as usize));
+            directories.push(directory);
+        }
+
+        Ok(directories)
+    }
+}
+
+/// Decodes the color filter array data of the given RAW file into an RGB image.
+pub fn open(data: &[u8]) -> Result<DynamicImage> {
+    let little_endian = match data.get(.. 4) {
+        Some([b'I', b'I', 42, 0]) => true,
+        Some([b'M', b'M', 0, 42]) => false,
+        _ => return Err(Error::decode("only TIFF-based RAW files are supported")),
+    };
+    let tiff = Tiff { data, little_endian };
+
+    let directory = tiff
+        .directories()?
+        .into_iter()
+        .find(|directory| self::value(directory, TAG_PHOTOMETRIC_INTERPRETATION) == Some(PHOTOMETRIC_CFA as f64))
+        .ok_or_else(|| Error::decode("file does not contain color filter array data"))?;
+
+    if self::value(&directory, TAG_COMPRESSION).unwrap_or(COMPRESSION_NONE as f64) != COMPRESSION_NONE as f64 {
+        return Err(Error::decode("only uncompressed RAW data is supported"));
+    }
+
+    if directory.contains_key(&TAG_TILE_OFFSETS) {
+        return Err(Error::decode("only RAW data stored in strips is supported"));
+    }
+
+    let width = self::value(&directory, TAG_WIDTH).ok_or_else(|| Error::decode("missing image width"))? as u32;
+    let height = self::value(&directory, TAG_HEIGHT).ok_or_else(|| Error::decode("missing image height"))? as u32;
+    let bits_per_sample = self::value(&directory, TAG_BITS_PER_SAMPLE).unwrap_or(16.0) as u32;
+
+    if !matches!(bits_per_sample, 8 | 16) {
+        return Err(Error::decode(format!("{bits_per_sample}-bit RAW data is not supported")));
+    }
+
+    let sample_count =
+        (width as usize).checked_mul(height as usize).ok_or_else(|| Error::decode("image is too large"))?;
+    let strips = directory.get(&TAG_STRIP_OFFSETS).into_iter().flatten();
+    let lengths = directory.get(&TAG_STRIP_BYTE_COUNTS).into_iter().flatten();
+
+    // The dimensions are only trusted once the strips are known to hold that many samples, so that a corrupt header
+    // cannot cause an enormous allocation.
+    let strip_bytes = lengths.clone().map(|length| *length as usize).fold(0, usize::saturating_add);
+
+    if sample_count.checked_mul(bits_per_sample as usize / 8).is_none_or(|bytes| bytes > strip_bytes.min(data.len())) {
+        return Err(Error::decode("RAW data is truncated"));
+    }
+
+    let mut samples = Vec::with_capacity(sample_count);
+
+    for (offset, length) in strips.zip(lengths).map(|(offset, length)| (*offset as usize, *length as usize)) {
+        let strip = data.get(offset .. offset + length).ok_or_else(|| Error::decode("strip is out of bounds"))?;
+
+        if bits_per_sample == 8 {
+            samples.extend(strip.iter().map(|sample| *sample as f32));
+        } else if length % 2 != 0 {
+            return Err(Error::decode("strip ends partway through a sample"));
+        } else {
+            for index in 0 .. length / 2 {
+                samples.push(tiff.u16_at(offset + index * 2)? as f32);
+            }
+        }
+    }
+
+    if samples.len() < sample_count {
+        return Err(Error::decode("RAW data is truncated"));
+    }
+
+    let pattern: [usize; 4] = match directory.get(&TAG_CFA_PATTERN).map(|pattern| &pattern[..]) {
+        Some(&[a, b, c, d]) => [a as usize, b as usize, c as usize, d as usize],
+        _ => [0, 1, 1, 2],
+    };
+
+    if pattern.iter().any(|color| *color >= 3) {
+        return Err(Error::decode("only red, green and blue color filters are supported"));
+    }
+
+    let black_level = self::value(&directory, TAG_BLACK_LEVEL).unwrap_or(0.0) as f32;
+    let white_level =
+        self::value(&directory, TAG_WHITE_LEVEL).unwrap_or((1_u32 << bits_per_sample) as f64 - 1.0) as f32;
+    let neutral = match directory.get(&TAG_AS_SHOT_NEUTRAL).map(|neutral| &neutral[..]) {
+        Some(&[r, g, b]) if r > 0.0 && g > 0.0 && b > 0.0 => [r as f32, g as f32, b as f32],
+        _ => [1.0; 3],
+    };
+
+    let image = RgbImage::from_fn(width / 2, height / 2, |x, y| {
+        let mut totals = [0.0_f32; 3];
+        let mut counts = [0.0_f32; 3];
+
+        for (index, color) in pattern.iter().copied().enumerate() {
+            let sample_x = x as usize * 2 + index % 2;
+            let sample_y = y as usize * 2 + index / 2;
+
+            totals[color] += samples[sample_y * width as usize + sample_x];
+            counts[color] += 1.0;
+        }
+
+        image::Rgb(std::array::from_fn(|color| {
+            let value = (totals[color] / counts[color].max(1.0) - black_level) / (white_level - black_level).max(1.0);
+            // Divide by the neutral color to white balance, then apply a simple gamma curve to brighten the shadows.
+            let balanced = (value * neutral[1] / neutral[color]).clamp(0.0, 1.0);
+
+            (balanced.powf(1.0 / 2.2) * u8::MAX as f32).round() as u8
+        }))
+    });
+
+    Ok(DynamicImage::ImageRgb8(image))
+}
+
+fn value(directory: &Directory, tag: u16) -> Option<f64> {
+    directory.get(&tag).and_then(|values| values.first().copied())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+
+    /// Returns a little-endian TIFF file with one directory of the given tags, each holding a single long value.
+    fn tiff(tags: &[(u16, u32)]) -> Vec<u8> {
+        let mut data = vec![b'I', b'I', 42, 0, 8, 0, 0, 0];
+
+        data.extend((tags.len() as u16).to_le_bytes());
+
+        for (tag, value) in tags {
+            data.extend(tag.to_le_bytes());
+            data.extend(4_u16.to_le_bytes());
+            data.extend(1_u32.to_le_bytes());
+            data.extend(value.to_le_bytes());
+        }
+
+        data.extend(0_u32.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn dimensions_are_checked_against_the_strips() {
+        let data = self::tiff(&[
+            (super::TAG_WIDTH, 100_000),
+            (super::TAG_HEIGHT, 100_000),
+            (super::TAG_BITS_PER_SAMPLE, 16),
+            (super::TAG_PHOTOMETRIC_INTERPRETATION, super::PHOTOMETRIC_CFA as u32),
+            (super::TAG_STRIP_OFFSETS, 0),
+            (super::TAG_STRIP_BYTE_COUNTS, 8),
+        ]);
+
+        assert!(matches!(super::open(&data), Err(Error::Decode(_))));
+    }
+
+    #[test]
+    fn strips_are_demosaiced() {
+        // The directory of six entries ends at byte 86, where the samples of a single 2x2 block follow.
+        let mut data = self::tiff(&[
+            (super::TAG_WIDTH, 2),
+            (super::TAG_HEIGHT, 2),
+            (super::TAG_BITS_PER_SAMPLE, 16),
+            (super::TAG_PHOTOMETRIC_INTERPRETATION, super::PHOTOMETRIC_CFA as u32),
+            (super::TAG_STRIP_OFFSETS, 86),
+            (super::TAG_STRIP_BYTE_COUNTS, 8),
+        ]);
+
+        data.extend([u16::MAX, 0, 0, 0].into_iter().flat_map(u16::to_le_bytes));
+
+        let image = super::open(&data).expect("the image should be decoded").into_rgb8();
+
+        assert_eq!(image.dimensions(), (1, 1));
+        assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0]);
+    }
+}
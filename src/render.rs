@@ -0,0 +1,899 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Images drawn as frames of colored characters.
+
+use std::borrow::Cow;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::Write;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use crossterm::style::{Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{Clear, ClearType};
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, Luma, LumaA, Pixel, Rgb, Rgba};
+
+use crate::buffer::{Cell, FrameBuffer, RowCache};
+use crate::cache::FontBundle;
+use crate::cli::RenderArguments;
+use crate::color::{ColorDepth, ColorPipeline, ColorTarget, ColorTransform};
+use crate::grid::ModuleGrid;
+use crate::measure::{CharacterMap, MAX_BRIGHTNESS};
+use crate::source::SourceImage;
+use crate::{color, measure, resample, terminal};
+
+const SMOOTH_RAMP_LEVELS: u16 = 16;
+
+const PALETTE_SAMPLES: usize = 1 << 16;
+
+const BAND_AREA_THRESHOLD: u32 = 1 << 16;
+
+const BAND_HEIGHT: u32 = 32;
+
+pub const ROW_CACHE_CAPACITY: usize = 1 << 10;
+
+/// The style used to draw a badge.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum BadgeStyle {
+    /// One row of characters chosen by brightness.
+    Glyphs,
+    /// One row of half-block characters, each drawing two pixels.
+    HalfBlocks,
+    /// One row of characters chosen by brightness that spans the full width, with each character representing the
+    /// average of an entire column of the image.
+    Sparkline,
+}
+
+/// The point of the terminal at which an image is placed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Anchor {
+    /// The top left corner.
+    #[default]
+    TopLeft,
+    /// The center of the top edge.
+    Top,
+    /// The top right corner.
+    TopRight,
+    /// The center of the left edge.
+    Left,
+    /// The center of the terminal.
+    Center,
+    /// The center of the right edge.
+    Right,
+    /// The bottom left corner.
+    BottomLeft,
+    /// The center of the bottom edge.
+    Bottom,
+    /// The bottom right corner.
+    BottomRight,
+}
+
+impl Anchor {
+    pub fn offset(self, (width, height): (u16, u16), (used_width, used_height): (u32, u32)) -> (u16, u16) {
+        let (column, row) = match self {
+            Self::TopLeft => (0, 0),
+            Self::Top => (1, 0),
+            Self::TopRight => (2, 0),
+            Self::Left => (0, 1),
+            Self::Center => (1, 1),
+            Self::Right => (2, 1),
+            Self::BottomLeft => (0, 2),
+            Self::Bottom => (1, 2),
+            Self::BottomRight => (2, 2),
+        };
+
+        let free_width = (width as u32).saturating_sub(used_width);
+        let free_height = (height as u32).saturating_sub(used_height);
+
+        ((free_width * column / 2) as u16, (free_height * row / 2) as u16)
+    }
+}
+
+/// How the rows between scanlines are drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ScanlineStyle {
+    /// The rows are left blank.
+    #[default]
+    Blank,
+    /// The rows are drawn at half of their opacity.
+    Dim,
+}
+
+/// How pixels are sampled when an image is resized.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SampleFilter {
+    /// Neighboring pixels are blended together, producing smooth gradients.
+    #[default]
+    Bilinear,
+    /// The closest pixel is used as-is, keeping edges crisp.
+    Nearest,
+}
+
+impl SampleFilter {
+    pub const fn filter_type(self) -> FilterType {
+        match self {
+            Self::Bilinear => FilterType::Triangle,
+            Self::Nearest => FilterType::Nearest,
+        }
+    }
+}
+
+/// How the terminal is cleared before drawing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ClearMode {
+    /// Nothing is cleared, drawing over the existing contents.
+    None,
+    /// The entire terminal is cleared.
+    All,
+    /// Everything from the cursor downwards is cleared.
+    Below,
+}
+
+impl ClearMode {
+    pub fn queue(self, stdout: &mut impl Write) -> Result<()> {
+        match self {
+            Self::None => Ok(()),
+            Self::All => crossterm::queue!(stdout, Clear(ClearType::All)).map_err(Into::into),
+            Self::Below => crossterm::queue!(stdout, Clear(ClearType::FromCursorDown)).map_err(Into::into),
+        }
+    }
+}
+
+/// The quality at which images are resized, from highest to lowest.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Quality {
+    /// Images are resized as configured.
+    #[default]
+    Full,
+    /// Images are always downscaled before they are stretched.
+    Reduced,
+    /// Images are downscaled before they are stretched, using nearest-neighbor sampling.
+    Nearest,
+}
+
+impl Quality {
+    pub const fn reduced(self) -> Option<Self> {
+        match self {
+            Self::Full => Some(Self::Reduced),
+            Self::Reduced => Some(Self::Nearest),
+            Self::Nearest => None,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::Reduced => "reduced",
+            Self::Nearest => "nearest-neighbor",
+        }
+    }
+}
+
+/// The settings used when drawing an image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DrawSettings {
+    pub character_map: CharacterMap,
+    pub fallback_character: char,
+    pub levels: Option<u16>,
+    pub supersample: Option<u32>,
+    pub downsample_gamma_correct: bool,
+    pub blur: f32,
+    pub alpha_threshold: u8,
+    pub color_pipeline: Option<ColorPipeline>,
+    pub compare_colors: bool,
+    pub background: [u8; 3],
+    pub auto_invert: bool,
+    pub clear_mode: ClearMode,
+    pub scale: f32,
+    pub stretch_to_fill: bool,
+    pub downscale_first: bool,
+    pub thumbnail: bool,
+    pub block_threshold: Option<u8>,
+    pub quality: Quality,
+    pub luma_filter: SampleFilter,
+    pub color_filter: SampleFilter,
+    pub color_interval: u16,
+    pub anchor: Anchor,
+    pub max_colors: Option<u16>,
+    pub shadow_offset: Option<(u16, u16)>,
+    pub shadow_opacity: f32,
+    pub pad: bool,
+    pub line_spacing: u16,
+    pub scanline_style: ScanlineStyle,
+    pub legend: Option<Box<[(char, u16)]>>,
+    pub color_target: ColorTarget,
+    pub color_grayscale: bool,
+}
+
+impl DrawSettings {
+    pub fn scaled_size(&self, (width, height): (u16, u16)) -> (u16, u16) {
+        let scale = |length: u16| (length as f32 * self.scale).round().clamp(1.0, u16::MAX as f32) as u16;
+
+        (scale(width), scale(height))
+    }
+
+    /// Returns the luma that the brightness of characters is measured against, which is the background's unless the
+    /// ramp is never inverted, in which case characters are chosen as though they were drawn on black.
+    pub fn contrast_luma(&self) -> u8 {
+        let Luma([background_luma]) = Rgb(self.background).to_luma();
+
+        if self.auto_invert { background_luma } else { 0 }
+    }
+
+    /// Returns the color pipeline used to draw the given image, if it is drawn in color.
+    pub fn image_color_pipeline(&self, source_image: &DynamicImage) -> Option<&ColorPipeline> {
+        self.color_pipeline
+            .as_ref()
+            .filter(|_| self.color_grayscale || self.compare_colors || source_image.color().has_color())
+    }
+}
+
+pub fn draw_settings(arguments: &RenderArguments, clear_mode: ClearMode, query_terminal: bool) -> Result<DrawSettings> {
+    let character_map = match (arguments.mono_threshold_map.as_deref(), arguments.import_font_cache.as_deref()) {
+        (Some(path), _) => CharacterMap::Thresholds(measure::load_thresholds(path)?),
+        (None, Some(path)) => CharacterMap::Nearest(FontBundle::read(path)?.brightnesses),
+        (None, None) => {
+            let (fonts, charset, raster_settings) = measure::measure_settings(&arguments.measure);
+
+            let brightnesses = measure::compute_brightnesses(
+                &mut measure::glyph_cache(),
+                &fonts,
+                &charset,
+                raster_settings,
+                arguments.measure.all_glyphs,
+                arguments.measure.normalize_percentile,
+                arguments.measure.parallelism(),
+            )?;
+
+            CharacterMap::Nearest(brightnesses)
+        }
+    };
+    let character_map = if arguments.smooth { character_map.smoothed(SMOOTH_RAMP_LEVELS) } else { character_map };
+
+    if character_map.is_empty() {
+        eprintln!(
+            "warning: no characters are available, so every pixel will be drawn as '{}'",
+            arguments.fallback_char
+        );
+    }
+
+    let background =
+        arguments.terminal_bg.or_else(|| query_terminal.then(terminal::background_color).flatten()).unwrap_or_default();
+    let color_pipeline = (!arguments.plain).then(|| {
+        let mut color_pipeline = ColorPipeline::new(arguments.color_depth, arguments.color_distance, background);
+
+        if arguments.invert_colors {
+            color_pipeline.push(ColorTransform::Invert);
+        }
+
+        color_pipeline.push(ColorTransform::Saturate(arguments.saturation));
+        color_pipeline.push(ColorTransform::RotateHue(arguments.hue_shift));
+
+        if let Some(color_blindness) = arguments.simulate {
+            color_pipeline.push(ColorTransform::Simulate(color_blindness));
+        }
+
+        color_pipeline
+    });
+
+    let legend = arguments.legend.then(|| character_map.ramp().into_boxed_slice());
+
+    Ok(DrawSettings {
+        character_map,
+        fallback_character: arguments.fallback_char,
+        levels: arguments.levels,
+        supersample: arguments.supersample,
+        downsample_gamma_correct: arguments.downsample_gamma_correct,
+        blur: arguments.blur,
+        alpha_threshold: arguments.antialias_threshold,
+        color_pipeline,
+        compare_colors: arguments.compare_colors,
+        background,
+        auto_invert: !arguments.no_auto_invert,
+        clear_mode,
+        scale: arguments.scale,
+        stretch_to_fill: arguments.stretch_to_fill,
+        downscale_first: arguments.downscale_first,
+        thumbnail: arguments.thumbnail,
+        block_threshold: arguments.block_grid.then_some(arguments.block_threshold),
+        quality: Quality::Full,
+        luma_filter: arguments.luma_filter,
+        color_filter: arguments.color_filter,
+        color_interval: arguments.color_interval,
+        anchor: arguments.anchor,
+        max_colors: arguments.max_colors,
+        shadow_offset: arguments.shadow,
+        shadow_opacity: arguments.shadow_opacity,
+        pad: arguments.pad,
+        line_spacing: arguments.line_spacing,
+        scanline_style: arguments.scanline_style,
+        legend,
+        color_target: arguments.color_target,
+        color_grayscale: arguments.color_grayscale,
+    })
+}
+
+pub fn draw_image(
+    stdout: &mut impl Write,
+    draw_settings: &DrawSettings,
+    source_image: &SourceImage,
+    terminal_size: (u16, u16),
+    previous_frame: &mut Option<FrameBuffer>,
+    row_cache: &mut RowCache,
+    overlays: &[(Anchor, &[Box<str>])],
+) -> Result<()> {
+    let frame = self::render_frame(draw_settings, source_image, terminal_size, row_cache);
+
+    self::present_frame(stdout, draw_settings, frame, previous_frame, overlays)
+}
+
+pub fn present_frame(
+    stdout: &mut impl Write,
+    draw_settings: &DrawSettings,
+    mut frame: FrameBuffer,
+    previous_frame: &mut Option<FrameBuffer>,
+    overlays: &[(Anchor, &[Box<str>])],
+) -> Result<()> {
+    let terminal_size = frame.size();
+
+    // Overlays are drawn into the frame itself, so hiding them again only rewrites the cells that they covered.
+    for (anchor, lines) in overlays {
+        let box_size = FrameBuffer::text_box_size(lines);
+        let offset = anchor.offset(terminal_size, (box_size.0 as u32, box_size.1 as u32));
+
+        frame.draw_text_box(offset, lines);
+    }
+    let previous_frame_ref = previous_frame.as_ref().filter(|previous_frame| previous_frame.size() == frame.size());
+
+    // Rather than clearing the whole terminal, which causes a visible flash, the undrawn cells are written as blanks.
+    if previous_frame_ref.is_none() && draw_settings.clear_mode == ClearMode::Below {
+        draw_settings.clear_mode.queue(stdout)?;
+    }
+
+    frame.queue(stdout, previous_frame_ref, draw_settings.clear_mode == ClearMode::All)?;
+
+    *previous_frame = Some(frame);
+
+    stdout.flush().map_err(Into::into)
+}
+
+pub fn scroll_size(source_image: &DynamicImage, terminal_width: u16) -> (u16, u16) {
+    // Each cell covers twice as many source pixels vertically as horizontally.
+    let rows = (source_image.height() as u64 * terminal_width as u64).div_ceil(source_image.width() as u64 * 2);
+
+    (terminal_width, rows.clamp(1, u16::MAX as u64) as u16)
+}
+
+pub fn render_frame(
+    draw_settings: &DrawSettings,
+    source_image: &SourceImage,
+    frame_size: (u16, u16),
+    row_cache: &mut RowCache,
+) -> FrameBuffer {
+    let SourceImage { image: source_image, transparent } = source_image;
+    let mut frame = FrameBuffer::new(frame_size);
+    let mut color_pipeline = draw_settings.image_color_pipeline(source_image).cloned();
+    let legend_width =
+        draw_settings.legend.as_deref().map_or(0, |legend| self::draw_legend(&mut frame, draw_settings, legend));
+    let frame_size = (frame_size.0 - legend_width, frame_size.1);
+
+    if let (Some(color_pipeline), Some(max_colors)) = (&mut color_pipeline, draw_settings.max_colors) {
+        color_pipeline.set_palette(self::image_palette(source_image, max_colors as usize));
+    }
+
+    if draw_settings.compare_colors {
+        let tile_width = frame_size.0 / 3;
+        // Tiles without any columns would each be drawn over the others at the left edge of the frame, so nothing is
+        // drawn when it is too narrow to compare colors.
+        let depths =
+            if tile_width > 0 { &[ColorDepth::TrueColor, ColorDepth::Ansi256, ColorDepth::Ansi16][..] } else { &[] };
+
+        let mut color_pipeline = color_pipeline.unwrap_or_default();
+
+        for (index, depth) in depths.iter().copied().enumerate() {
+            let offset = (index as u16 * tile_width, 0);
+
+            color_pipeline.depth = depth;
+
+            self::draw_ascii_image(
+                &mut frame,
+                draw_settings,
+                source_image,
+                offset,
+                (tile_width, frame_size.1),
+                Some(&color_pipeline),
+                row_cache,
+            );
+        }
+    } else if let Some(threshold) = draw_settings.block_threshold {
+        self::draw_block_grid(&mut frame, draw_settings, source_image, frame_size, threshold);
+    } else {
+        self::draw_ascii_image(
+            &mut frame,
+            draw_settings,
+            source_image,
+            (0, 0),
+            frame_size,
+            color_pipeline.as_ref(),
+            row_cache,
+        );
+    }
+
+    // Otherwise nothing at all would be drawn, which is indistinguishable from failing to draw the image.
+    if *transparent {
+        let lines: [Box<str>; 1] = ["The image is fully transparent".into()];
+        let box_size = FrameBuffer::text_box_size(&lines);
+        let offset = Anchor::Center.offset(frame_size, (box_size.0 as u32, box_size.1 as u32));
+
+        frame.draw_text_box(offset, &lines);
+    }
+
+    if draw_settings.pad {
+        frame.fill(Cell::BLANK);
+    }
+
+    frame
+}
+
+fn draw_block_grid(
+    frame: &mut FrameBuffer,
+    draw_settings: &DrawSettings,
+    source_image: &DynamicImage,
+    size: (u16, u16),
+    threshold: u8,
+) {
+    let grid = ModuleGrid::from_image(source_image, threshold, draw_settings.alpha_threshold);
+    let (columns, rows) = grid.size();
+    // Each module is drawn two cells wide, since cells are roughly twice as tall as they are wide.
+    let scale = (size.0 as u32 / (columns * 2).max(1)).min(size.1 as u32 / rows.max(1));
+
+    if scale == 0 {
+        let lines: [Box<str>; 1] = [format!("Too few cells to draw {columns}x{rows} modules").into()];
+        let box_size = FrameBuffer::text_box_size(&lines);
+        let offset = Anchor::Center.offset(size, (box_size.0 as u32, box_size.1 as u32));
+
+        frame.draw_text_box(offset, &lines);
+
+        return;
+    }
+
+    let drawn_size = (columns * 2 * scale, rows * scale);
+    let offset = draw_settings.anchor.offset(size, drawn_size);
+    // Codes are scanned as dark modules on a light background, so the modules that are lighter than the terminal's
+    // background are the ones filled.
+    let fill_dark = draw_settings.contrast_luma() > u8::MAX / 2;
+
+    for y in 0 .. drawn_size.1 {
+        for x in 0 .. drawn_size.0 {
+            let character = if grid.is_dark(x / (scale * 2), y / scale) == fill_dark { '█' } else { ' ' };
+
+            frame.set(offset.0 + x as u16, offset.1 + y as u16, Cell::plain(character));
+        }
+    }
+}
+
+/// Draws the given ramp of characters against the right edge of the frame, returning the number of columns it covers.
+fn draw_legend(frame: &mut FrameBuffer, draw_settings: &DrawSettings, legend: &[(char, u16)]) -> u16 {
+    let (frame_width, frame_height) = frame.size();
+    // Ramps taller than the frame are sampled evenly, always keeping the darkest and brightest characters.
+    let rows = legend.len().min(frame_height as usize);
+    let entries: Vec<(char, u16)> =
+        (0 .. rows).map(|row| legend[if rows > 1 { row * (legend.len() - 1) / (rows - 1) } else { 0 }]).collect();
+    let labels: Vec<String> = entries
+        .iter()
+        .map(|(_, brightness)| format!("{:>3}%", *brightness as u32 * 100 / MAX_BRIGHTNESS as u32))
+        .collect();
+    // Each row is a character, a space, and its label, separated from the image by a column of blanks.
+    let width = labels.iter().map(String::len).max().map_or(0, |width| width as u16 + 3);
+
+    if entries.is_empty() || width >= frame_width {
+        return 0;
+    }
+
+    let background_luma = draw_settings.contrast_luma();
+    let x = frame_width - width + 1;
+
+    for (y, ((character, brightness), label)) in entries.into_iter().zip(labels).enumerate() {
+        // The character is shaded with the contrast against the background that its brightness represents.
+        let contrast = (brightness as u32 * u8::MAX as u32 / MAX_BRIGHTNESS as u32) as u8;
+        let shade = if background_luma < 0x80 {
+            background_luma.saturating_add(contrast)
+        } else {
+            background_luma.saturating_sub(contrast)
+        };
+        let color = draw_settings
+            .color_pipeline
+            .as_ref()
+            .map(|color_pipeline| color_pipeline.depth.color([shade; 3], color_pipeline.distance));
+
+        frame.set(x, y as u16, Cell { character, color, target: ColorTarget::Foreground });
+
+        for (offset, character) in (2 ..).zip(label.chars()) {
+            frame.set(x + offset, y as u16, Cell::plain(character));
+        }
+    }
+
+    width
+}
+
+fn draw_ascii_image(
+    frame: &mut FrameBuffer,
+    draw_settings: &DrawSettings,
+    source_image: &DynamicImage,
+    offset: (u16, u16),
+    size: (u16, u16),
+    color_pipeline: Option<&ColorPipeline>,
+    row_cache: &mut RowCache,
+) {
+    let stretched_width = source_image.width() * 2;
+    let scaled_size = self::scaled_image_size(draw_settings, source_image, size);
+    let anchor_offset = draw_settings.anchor.offset(size, scaled_size);
+    let offset = (offset.0 + anchor_offset.0, offset.1 + anchor_offset.1);
+    let source_image = resample::premultiply(source_image);
+    // Blurring the premultiplied image keeps the colors of transparent pixels from bleeding into their neighbors.
+    let source_image =
+        &*if draw_settings.blur > 0.0 { Cow::Owned(source_image.blur(draw_settings.blur)) } else { source_image };
+
+    // Very large terminals are drawn in bands to keep the peak memory usage of scaling bounded. Downscaling first never
+    // produces an image larger than the terminal, so it is always drawn at once.
+    let filter = if draw_settings.quality == Quality::Nearest { FilterType::Nearest } else { FilterType::Triangle };
+    let scaled_bands: Box<dyn Iterator<Item = (u32, DynamicImage)>> =
+        if draw_settings.thumbnail && draw_settings.quality != Quality::Nearest {
+            let scaled_image = source_image.thumbnail_exact(scaled_size.0, scaled_size.1);
+
+            Box::new(std::iter::once((0, scaled_image)))
+        } else if draw_settings.downscale_first || draw_settings.quality >= Quality::Reduced {
+            let scaled_image = source_image
+                .resize_exact(scaled_size.0.div_ceil(2), scaled_size.1, filter)
+                .resize_exact(scaled_size.0, scaled_size.1, filter);
+
+            Box::new(std::iter::once((0, scaled_image)))
+        } else if let Some(factor) = draw_settings.supersample {
+            let (width, height) = (scaled_size.0 * factor, scaled_size.1 * factor);
+            let sampled_image = source_image.resize_exact(width, height, draw_settings.luma_filter.filter_type());
+
+            Box::new(std::iter::once((0, DynamicImage::ImageRgba8(resample::average_blocks(&sampled_image, factor)))))
+        } else if draw_settings.luma_filter == SampleFilter::Nearest {
+            let scaled_image = source_image.resize_exact(scaled_size.0, scaled_size.1, FilterType::Nearest);
+
+            Box::new(std::iter::once((0, scaled_image)))
+        } else if draw_settings.downsample_gamma_correct {
+            // Banding is skipped, since resizing in linear light requires a full-precision copy of the image anyway.
+            let scaled_image = resample::resize_linear(source_image, scaled_size, FilterType::Triangle);
+
+            Box::new(std::iter::once((0, scaled_image)))
+        } else if size.0 as u32 * size.1 as u32 > BAND_AREA_THRESHOLD {
+            let bands = resample::bands(source_image, stretched_width, scaled_size, BAND_HEIGHT);
+
+            Box::new(bands.map(|(band_y, band)| (band_y, DynamicImage::ImageRgba8(band))))
+        } else {
+            let scaled_image = source_image
+                .resize_exact(stretched_width, source_image.height(), FilterType::Triangle)
+                .resize_exact(scaled_size.0, scaled_size.1, FilterType::Triangle);
+
+            Box::new(std::iter::once((0, scaled_image)))
+        };
+    // Colors are sampled from a second copy of the image when their filter differs or brightnesses are supersampled,
+    // which is kept whole since it is never larger than the terminal. Thumbnails always share a single copy.
+    let color_image = (color_pipeline.is_some()
+        && (draw_settings.color_filter != draw_settings.luma_filter || draw_settings.supersample.is_some())
+        && draw_settings.quality != Quality::Nearest
+        && !draw_settings.thumbnail)
+        .then(|| {
+            let filter = draw_settings.color_filter.filter_type();
+
+            resample::unpremultiply(source_image.resize_exact(scaled_size.0, scaled_size.1, filter))
+        });
+
+    for (band_y, scaled_image) in scaled_bands {
+        let luma = resample::unpremultiply(scaled_image);
+        let color =
+            color_image.as_ref().map(|color_image| color_image.crop_imm(0, band_y, luma.width(), luma.height()));
+        let scaled_band = ScaledBand { luma, color };
+        let band_offset = (offset.0, offset.1 + band_y as u16);
+
+        // Shadows are only ever offset downwards, so drawing each band's shadow before the band itself never draws a
+        // shadow over a previously drawn band. Shadows that fall entirely outside of the frame are skipped.
+        if let Some((shadow_x, shadow_y)) = draw_settings.shadow_offset
+            && let shadow_offset = (band_offset.0.saturating_add(shadow_x), band_offset.1.saturating_add(shadow_y))
+            && shadow_offset.0 < frame.size().0
+            && shadow_offset.1 < frame.size().1
+        {
+            self::draw_ascii_band(
+                frame,
+                draw_settings,
+                &scaled_band,
+                shadow_offset,
+                color_pipeline,
+                row_cache,
+                draw_settings.shadow_opacity,
+            );
+        }
+
+        self::draw_ascii_band(frame, draw_settings, &scaled_band, band_offset, color_pipeline, row_cache, 1.0);
+    }
+}
+
+/// A band of an image scaled to the size at which it is drawn, along with a copy used to choose colors if they are
+/// sampled differently.
+struct ScaledBand {
+    luma: DynamicImage,
+    color: Option<DynamicImage>,
+}
+
+fn image_palette(source_image: &DynamicImage, max_colors: usize) -> Box<[[u8; 3]]> {
+    let source_image = source_image.to_rgba8();
+    // The palette only needs to represent the image's colors, so large images are sampled rather than read in full.
+    let stride = (source_image.width() as usize * source_image.height() as usize / PALETTE_SAMPLES).max(1);
+    let colors = source_image
+        .pixels()
+        .step_by(stride)
+        .filter(|Rgba([.., alpha])| *alpha > 0)
+        .map(|Rgba([red, green, blue, _])| [*red, *green, *blue])
+        .collect();
+
+    color::median_cut(colors, max_colors)
+}
+
+pub fn scaled_image_size(draw_settings: &DrawSettings, source_image: &DynamicImage, size: (u16, u16)) -> (u32, u32) {
+    let maximum_size = draw_settings.scaled_size(size);
+
+    if draw_settings.stretch_to_fill {
+        (maximum_size.0 as u32, maximum_size.1 as u32)
+    } else {
+        let stretched_size = (source_image.width() * 2, source_image.height());
+
+        resample::fit_dimensions(stretched_size, (maximum_size.0 as u32, maximum_size.1 as u32))
+    }
+}
+
+fn draw_ascii_band(
+    frame: &mut FrameBuffer,
+    draw_settings: &DrawSettings,
+    scaled_band: &ScaledBand,
+    offset: (u16, u16),
+    color_pipeline: Option<&ColorPipeline>,
+    row_cache: &mut RowCache,
+    opacity: f32,
+) {
+    let ScaledBand { luma: scaled_image, color: color_image } = scaled_band;
+    // Draw settings are shared by every band of a frame, so only the settings that differ between bands are hashed
+    // alongside each row's pixels.
+    let mut band_hasher = DefaultHasher::new();
+
+    (scaled_image.color(), scaled_image.width(), opacity.to_bits()).hash(&mut band_hasher);
+    color_pipeline.map(|color_pipeline| (color_pipeline.depth, color_pipeline.palette())).hash(&mut band_hasher);
+
+    for pixel_y in 0 .. scaled_image.height() {
+        let is_scanline = (offset.1 + pixel_y as u16).is_multiple_of(draw_settings.line_spacing);
+
+        if !is_scanline && draw_settings.scanline_style == ScanlineStyle::Blank {
+            continue;
+        }
+
+        let mut row_hasher = band_hasher.clone();
+
+        (is_scanline, self::row_bytes(scaled_image, pixel_y)).hash(&mut row_hasher);
+        color_image.as_ref().map(|color_image| self::row_bytes(color_image, pixel_y)).hash(&mut row_hasher);
+
+        let row = row_cache.get_or_insert_with(row_hasher.finish(), || {
+            (0 .. scaled_image.width())
+                .map(|pixel_x| {
+                    let pixel = scaled_image.get_pixel(pixel_x, pixel_y);
+                    let color_x = pixel_x - pixel_x % draw_settings.color_interval as u32;
+                    let color_pixel = color_image.as_ref().unwrap_or(scaled_image).get_pixel(color_x, pixel_y);
+
+                    (pixel, color_pixel.to_rgb())
+                })
+                .map(|(mut pixel, color)| {
+                    if pixel.0[3] <= draw_settings.alpha_threshold {
+                        return None;
+                    }
+
+                    // Reducing the opacity blends the pixel towards the background, dimming both its character and
+                    // color.
+                    pixel.0[3] = (pixel.0[3] as f32 * opacity).round() as u8;
+
+                    if !is_scanline {
+                        pixel.0[3] /= 2;
+                    }
+
+                    Some(self::ascii_cell(draw_settings, pixel, color, color_pipeline))
+                })
+                .collect()
+        });
+
+        for (pixel_x, cell) in row.iter().enumerate().filter_map(|(pixel_x, cell)| Some((pixel_x, (*cell)?))) {
+            frame.set(offset.0 + pixel_x as u16, offset.1 + pixel_y as u16, cell);
+        }
+    }
+}
+
+fn row_bytes(image: &DynamicImage, pixel_y: u32) -> &[u8] {
+    let row_length = image.width() as usize * image.color().bytes_per_pixel() as usize;
+
+    &image.as_bytes()[pixel_y as usize * row_length ..][.. row_length]
+}
+
+fn ascii_cell(
+    draw_settings: &DrawSettings,
+    pixel: Rgba<u8>,
+    color: Rgb<u8>,
+    color_pipeline: Option<&ColorPipeline>,
+) -> Cell {
+    let LumaA([luma, alpha]) = pixel.to_luma_alpha();
+    let Luma([background_luma]) = Rgb(draw_settings.background).to_luma();
+    // Characters are drawn in the pixel's color over the background, so the amount of "ink" needed is the contrast
+    // between the blended pixel and the background rather than the pixel's absolute brightness.
+    let blended_luma = luma as u16 * alpha as u16 + background_luma as u16 * (u8::MAX - alpha) as u16;
+    let brightness = blended_luma.abs_diff(draw_settings.contrast_luma() as u16 * u8::MAX as u16);
+    let brightness = match draw_settings.levels {
+        Some(levels) => {
+            let steps = (levels - 1) as u32;
+            let level = (brightness as u32 * steps + MAX_BRIGHTNESS as u32 / 2) / MAX_BRIGHTNESS as u32;
+
+            (level * MAX_BRIGHTNESS as u32 / steps) as u16
+        }
+        None => brightness,
+    };
+    let character = draw_settings.character_map.character(brightness).unwrap_or(draw_settings.fallback_character);
+    let color = color_pipeline.map(|color_pipeline| color_pipeline.color(color.0, alpha));
+
+    Cell { character, color, target: draw_settings.color_target }
+}
+
+pub fn draw_badge(
+    stdout: &mut impl Write,
+    draw_settings: &DrawSettings,
+    source_image: &DynamicImage,
+    badge_style: BadgeStyle,
+    maximum_width: u16,
+    dither: bool,
+) -> Result<()> {
+    let color_pipeline = draw_settings.image_color_pipeline(source_image);
+    let source_image = &*resample::premultiply(source_image);
+
+    draw_settings.clear_mode.queue(stdout)?;
+
+    match badge_style {
+        BadgeStyle::Glyphs | BadgeStyle::Sparkline => {
+            let scaled_image = if badge_style == BadgeStyle::Sparkline {
+                // The columns are averaged exactly rather than resized, so that each one weighs every row equally.
+                let columns =
+                    source_image.resize_exact(maximum_width as u32, source_image.height(), FilterType::Triangle);
+
+                resample::unpremultiply(DynamicImage::ImageRgba8(resample::average_columns(&columns)))
+            } else {
+                resample::unpremultiply(
+                    source_image
+                        .resize_exact(source_image.width() * 2, source_image.height(), FilterType::Triangle)
+                        .resize(maximum_width as u32, 1, FilterType::Triangle),
+                )
+            };
+
+            for pixel in (0 .. scaled_image.width()).map(|pixel_x| scaled_image.get_pixel(pixel_x, 0)) {
+                if pixel.0[3] <= draw_settings.alpha_threshold {
+                    crossterm::queue!(stdout, ResetColor, Print(' '))?;
+                } else {
+                    let cell = self::ascii_cell(draw_settings, pixel, pixel.to_rgb(), color_pipeline);
+
+                    cell.queue_color(stdout, None)?;
+
+                    crossterm::queue!(stdout, Print(cell.character))?;
+                }
+            }
+        }
+        BadgeStyle::HalfBlocks => {
+            let scaled_image =
+                resample::unpremultiply(source_image.resize(maximum_width as u32, 2, FilterType::Triangle));
+            let color_pipeline = color_pipeline.cloned().unwrap_or_default();
+            let dithered_colors = dither.then(|| color_pipeline.dither(&scaled_image.to_rgba8()));
+            let color = |pixel_x: u32, pixel_y: u32, pixel: Rgba<u8>| match &dithered_colors {
+                Some(colors) => colors[(pixel_y * scaled_image.width() + pixel_x) as usize],
+                None => color_pipeline.color([pixel.0[0], pixel.0[1], pixel.0[2]], pixel.0[3]),
+            };
+
+            for pixel_x in 0 .. scaled_image.width() {
+                let upper = scaled_image.get_pixel(pixel_x, 0);
+                let lower_y = scaled_image.height() - 1;
+                let lower = scaled_image.get_pixel(pixel_x, lower_y);
+                let (upper_color, lower_color) = (color(pixel_x, 0, upper), color(pixel_x, lower_y, lower));
+
+                crossterm::queue!(stdout, ResetColor)?;
+
+                match (upper.0[3] > draw_settings.alpha_threshold, lower.0[3] > draw_settings.alpha_threshold) {
+                    (false, false) => crossterm::queue!(stdout, Print(' '))?,
+                    (true, false) => crossterm::queue!(stdout, SetForegroundColor(upper_color), Print('▀'))?,
+                    (false, true) => crossterm::queue!(stdout, SetForegroundColor(lower_color), Print('▄'))?,
+                    (true, true) => crossterm::queue!(
+                        stdout,
+                        SetForegroundColor(upper_color),
+                        SetBackgroundColor(lower_color),
+                        Print('▀')
+                    )?,
+                }
+            }
+        }
+    }
+
+    crossterm::queue!(stdout, ResetColor)?;
+
+    stdout.flush().map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use image::{DynamicImage, Rgba, RgbaImage};
+
+    use super::{ClearMode, DrawSettings, ROW_CACHE_CAPACITY};
+    use crate::buffer::{FrameBuffer, RowCache};
+    use crate::cli::{Arguments, Command};
+    use crate::source::SourceImage;
+
+    /// Returns the settings used to export an image with the given arguments, measured in the embedded font and drawn
+    /// against a black background so that they are the same on every machine.
+    fn draw_settings(arguments: &[&str]) -> DrawSettings {
+        let arguments = ["term-render", "export", "--test-pattern", "--font", "embedded", "--terminal-bg", "000000"]
+            .into_iter()
+            .chain(arguments.iter().copied());
+        let Command::Export(arguments) = Arguments::parse_from(arguments).command else {
+            unreachable!("only exports are parsed");
+        };
+
+        super::draw_settings(&arguments.render, ClearMode::None, false).expect("the settings should be valid")
+    }
+
+    fn frame(draw_settings: &DrawSettings, image: RgbaImage, size: (u16, u16)) -> FrameBuffer {
+        super::render_frame(
+            draw_settings,
+            &SourceImage::new(DynamicImage::ImageRgba8(image), draw_settings.alpha_threshold),
+            size,
+            &mut RowCache::new(ROW_CACHE_CAPACITY),
+        )
+    }
+
+    #[test]
+    fn transparent_images_draw_a_placeholder() {
+        let draw_settings = self::draw_settings(&[]);
+        let frame = self::frame(&draw_settings, RgbaImage::from_pixel(32, 32, Rgba([255, 255, 255, 0])), (40, 12));
+        let text: String = frame.drawn_cells().map(|cell| cell.character).collect();
+
+        assert!(text.contains("The image is fully transparent"), "the frame only contains {text:?}");
+    }
+
+    #[test]
+    fn shadows_outside_of_the_frame_are_skipped() {
+        let image = RgbaImage::from_pixel(32, 32, Rgba([255, 255, 255, 255]));
+        let unshadowed = self::frame(&self::draw_settings(&[]), image.clone(), (16, 8));
+        let shadowed = self::frame(&self::draw_settings(&["--shadow", "65535,65535"]), image, (16, 8));
+
+        assert!(shadowed.drawn_cells().eq(unshadowed.drawn_cells()), "the shadow should not be drawn");
+    }
+
+    #[test]
+    fn light_backgrounds_invert_the_ramp_unless_disabled() {
+        let image = RgbaImage::from_pixel(32, 32, Rgba([255, 255, 255, 255]));
+        let character = |arguments: &[&str]| {
+            let mut draw_settings = self::draw_settings(arguments);
+
+            draw_settings.background = [u8::MAX; 3];
+
+            self::frame(&draw_settings, image.clone(), (16, 8)).get(8, 4).map(|cell| cell.character)
+        };
+
+        // White has no contrast against a white background, so it is drawn with the least ink unless the ramp is kept.
+        assert_eq!(character(&[]), Some(' '));
+        assert_ne!(character(&["--no-auto-invert"]), Some(' '));
+    }
+}
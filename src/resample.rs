@@ -0,0 +1,299 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Banded image resampling.
+//!
+//! The functions within this module mirror the triangle-filtered resampling performed by the `image` crate, but
+//! produce the output one horizontal band at a time so that only the rows needed by each band are ever resident in
+//! memory. Because the arithmetic is performed in the same order, the output is identical to resizing the entire
+//! image at once.
+//!
+//! Images with transparency should be [premultiplied](premultiply) before resampling and
+//! [unpremultiplied](unpremultiply) afterwards, so that the colors of transparent pixels do not bleed into their
+//! neighbors.
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+use image::imageops::FilterType;
+use image::{DynamicImage, Rgba, Rgba32FImage, RgbaImage};
+
+use crate::color;
+
+/// Returns the largest dimensions that fit within the given bounds while preserving the aspect ratio.
+///
+/// This is equivalent to the calculation performed by [`DynamicImage::resize`].
+pub fn fit_dimensions((width, height): (u32, u32), (max_width, max_height): (u32, u32)) -> (u32, u32) {
+    let ratio = f64::min(max_width as f64 / width as f64, max_height as f64 / height as f64);
+    let fit_width = ((width as f64 * ratio).round() as u64).max(1);
+    let fit_height = ((height as f64 * ratio).round() as u64).max(1);
+
+    (fit_width.min(u32::MAX as u64) as u32, fit_height.min(u32::MAX as u64) as u32)
+}
+
+/// Returns the given image with its color channels multiplied by its alpha channel.
+///
+/// Images without an alpha channel are returned unchanged.
+pub fn premultiply(image: &DynamicImage) -> Cow<'_, DynamicImage> {
+    if !image.color().has_alpha() {
+        return Cow::Borrowed(image);
+    }
+
+    let mut image = image.to_rgba8();
+
+    for Rgba([red, green, blue, alpha]) in image.pixels_mut() {
+        for channel in [red, green, blue] {
+            *channel = ((*channel as u16 * *alpha as u16 + u8::MAX as u16 / 2) / u8::MAX as u16) as u8;
+        }
+    }
+
+    Cow::Owned(DynamicImage::ImageRgba8(image))
+}
+
+/// Returns the given premultiplied image with its color channels divided by its alpha channel.
+///
+/// Images without an alpha channel are returned unchanged.
+pub fn unpremultiply(image: DynamicImage) -> DynamicImage {
+    if !image.color().has_alpha() {
+        return image;
+    }
+
+    let mut image = image.into_rgba8();
+
+    for Rgba([red, green, blue, alpha]) in image.pixels_mut() {
+        for channel in [red, green, blue] {
+            *channel = match *alpha {
+                0 => 0,
+                alpha => {
+                    ((*channel as u16 * u8::MAX as u16 + alpha as u16 / 2) / alpha as u16).min(u8::MAX as u16) as u8
+                }
+            };
+        }
+    }
+
+    DynamicImage::ImageRgba8(image)
+}
+
+/// Returns the given premultiplied image resized in linear light rather than in sRGB, which keeps thin bright details
+/// from being darkened by their surroundings.
+///
+/// The resized image is also premultiplied.
+pub fn resize_linear(image: &DynamicImage, (width, height): (u32, u32), filter: FilterType) -> DynamicImage {
+    let straight = self::unpremultiply(image.clone()).into_rgba8();
+    let linear = Rgba32FImage::from_fn(straight.width(), straight.height(), |x, y| {
+        let Rgba([red, green, blue, alpha]) = *straight.get_pixel(x, y);
+        let alpha = alpha as f32 / u8::MAX as f32;
+
+        let [red, green, blue] = [red, green, blue].map(|channel| color::srgb_to_linear(channel) * alpha);
+
+        Rgba([red, green, blue, alpha])
+    });
+    let resized = image::imageops::resize(&linear, width, height, filter);
+
+    let image = RgbaImage::from_fn(width, height, |x, y| {
+        let Rgba([red, green, blue, alpha]) = *resized.get_pixel(x, y);
+        let alpha = alpha.clamp(0.0, 1.0);
+        // Each channel is divided by its alpha before being encoded, then premultiplied again once encoded.
+        let encode = |channel: f32| match alpha {
+            0.0 => 0,
+            _ => (color::linear_to_srgb(channel / alpha) as f32 * alpha).round() as u8,
+        };
+
+        Rgba([encode(red), encode(green), encode(blue), (alpha * u8::MAX as f32).round() as u8])
+    });
+
+    DynamicImage::ImageRgba8(image)
+}
+
+/// Returns the given image reduced by averaging each block of `factor` by `factor` pixels into a single pixel.
+///
+/// The image's dimensions must be multiples of the factor.
+pub fn average_blocks(image: &DynamicImage, factor: u32) -> RgbaImage {
+    let image = image.to_rgba8();
+    let area = factor * factor;
+
+    RgbaImage::from_fn(image.width() / factor, image.height() / factor, |x, y| {
+        let mut totals = [0_u32; 4];
+
+        for (block_x, block_y) in (0 .. area).map(|index| (index % factor, index / factor)) {
+            let Rgba(pixel) = image.get_pixel(x * factor + block_x, y * factor + block_y);
+
+            totals.iter_mut().zip(pixel).for_each(|(total, channel)| *total += *channel as u32);
+        }
+
+        Rgba(totals.map(|total| ((total + area / 2) / area) as u8))
+    })
+}
+
+/// Returns a single row of pixels, each the average of the entire column of the given image above it.
+pub fn average_columns(image: &DynamicImage) -> RgbaImage {
+    let image = image.to_rgba8();
+    let height = image.height().max(1) as u64;
+
+    RgbaImage::from_fn(image.width(), 1, |x, _| {
+        let mut totals = [0_u64; 4];
+
+        for Rgba(pixel) in (0 .. image.height()).map(|y| image.get_pixel(x, y)) {
+            totals.iter_mut().zip(pixel).for_each(|(total, channel)| *total += *channel as u64);
+        }
+
+        Rgba(totals.map(|total| ((total + height / 2) / height) as u8))
+    })
+}
+
+/// Returns an iterator over horizontal bands of the given image, each at most `band_height` rows tall.
+///
+/// The image is first stretched to `stretched_width` and then resized to `size`, yielding each band alongside the row
+/// at which it starts.
+pub fn bands(
+    image: &DynamicImage,
+    stretched_width: u32,
+    size: (u32, u32),
+    band_height: u32,
+) -> impl Iterator<Item = (u32, RgbaImage)> {
+    (0 .. size.1).step_by(band_height.max(1) as usize).map(move |band_y| {
+        (band_y, self::resize_band(image, stretched_width, size, band_y .. (band_y + band_height).min(size.1)))
+    })
+}
+
+fn resize_band(image: &DynamicImage, stretched_width: u32, (width, height): (u32, u32), rows: Range<u32>) -> RgbaImage {
+    let row_weights: Box<[(u32, Box<[f32]>)]> =
+        rows.clone().map(|row| self::sample_weights(image.height(), height, row)).collect();
+    let column_weights: Box<[(u32, Box<[f32]>)]> =
+        (0 .. width).map(|column| self::sample_weights(stretched_width, width, column)).collect();
+
+    let first_row = row_weights.first().map_or(0, |(start, _)| *start);
+    let last_row = row_weights.last().map_or(0, |(start, weights)| start + weights.len() as u32);
+
+    // Stretching is performed on each row independently, so only the rows within this band need to be stretched.
+    let source_rows = image.crop_imm(0, first_row, image.width(), last_row - first_row);
+    let stretched = source_rows.resize_exact(stretched_width, source_rows.height(), FilterType::Triangle).into_rgba8();
+
+    let mut vertical = vec![[0.0_f32; 4]; stretched_width as usize * row_weights.len()];
+
+    for (index, (start, weights)) in row_weights.iter().enumerate() {
+        for x in 0 .. stretched_width {
+            let mut total = [0.0_f32; 4];
+
+            for (offset, weight) in weights.iter().enumerate() {
+                let Rgba(pixel) = stretched.get_pixel(x, start - first_row + offset as u32);
+
+                for channel in 0 .. 4 {
+                    total[channel] += pixel[channel] as f32 * weight;
+                }
+            }
+
+            vertical[index * stretched_width as usize + x as usize] = total;
+        }
+    }
+
+    RgbaImage::from_fn(width, row_weights.len() as u32, |x, y| {
+        let (start, weights) = &column_weights[x as usize];
+        let mut total = [0.0_f32; 4];
+
+        for (offset, weight) in weights.iter().enumerate() {
+            let pixel = vertical[y as usize * stretched_width as usize + (start + offset as u32) as usize];
+
+            for channel in 0 .. 4 {
+                total[channel] += pixel[channel] * weight;
+            }
+        }
+
+        Rgba(total.map(|channel| channel.clamp(0.0, u8::MAX as f32).round() as u8))
+    })
+}
+
+fn sample_weights(input_size: u32, output_size: u32, output_index: u32) -> (u32, Box<[f32]>) {
+    let ratio = input_size as f32 / output_size as f32;
+    let support_ratio = if ratio < 1.0 { 1.0 } else { ratio };
+
+    let center = (output_index as f32 + 0.5) * ratio;
+    let start = ((center - support_ratio).floor() as i64).clamp(0, input_size as i64 - 1) as u32;
+    let end = ((center + support_ratio).ceil() as i64).clamp(start as i64 + 1, input_size as i64) as u32;
+
+    let center = center - 0.5;
+    let mut weights: Box<[f32]> = (start .. end)
+        .map(|index| {
+            let distance = ((index as f32 - center) / support_ratio).abs();
+
+            if distance < 1.0 { 1.0 - distance } else { 0.0 }
+        })
+        .collect();
+    let total = weights.iter().fold(0.0, |total, weight| total + weight);
+
+    weights.iter_mut().for_each(|weight| *weight /= total);
+
+    (start, weights)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::imageops::FilterType;
+    use image::{DynamicImage, Rgba, RgbaImage};
+
+    #[test]
+    fn premultiplication_round_trips() {
+        let image = RgbaImage::from_fn(16, 1, |x, _| Rgba([255, x as u8 * 16, 200 - x as u8 * 8, 128]));
+        let round_trip =
+            super::unpremultiply(super::premultiply(&DynamicImage::ImageRgba8(image.clone())).into_owned());
+
+        for (Rgba(expected), Rgba(actual)) in image.pixels().zip(round_trip.to_rgba8().pixels()) {
+            assert_eq!(expected[3], actual[3]);
+
+            for channel in 0 .. 3 {
+                assert!(expected[channel].abs_diff(actual[channel]) <= 1, "{expected:?} became {actual:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn transparent_colors_do_not_bleed() {
+        // Half-transparent red beside fully transparent green, which would tint the red if colors were not weighted by
+        // their opacity.
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(2, 1, |x, _| {
+            if x == 0 { Rgba([255, 0, 0, 128]) } else { Rgba([0, 255, 0, 0]) }
+        }));
+        let premultiplied = super::premultiply(&image);
+        let resized = super::unpremultiply(premultiplied.resize_exact(1, 1, FilterType::Triangle));
+        let Rgba([red, green, blue, alpha]) = *resized.to_rgba8().get_pixel(0, 0);
+
+        assert!(red > 250 && green == 0 && blue == 0, "the color became ({red}, {green}, {blue})");
+        assert!(alpha.abs_diff(64) <= 1, "the opacity became {alpha}");
+    }
+
+    #[test]
+    fn bands_match_resizing_the_whole_image() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(37, 23, |x, y| {
+            Rgba([(x * 7) as u8, (y * 11) as u8, ((x * y) % 256) as u8, 128 + (x + y) as u8 * 2])
+        }));
+
+        // The last size does not divide evenly into bands, which leaves a shorter band at the bottom.
+        for (size, band_height) in [((20, 10), 5), ((74, 23), 23), ((13, 7), 3)] {
+            let stretched_width = image.width() * 2;
+            let expected = image::imageops::resize(
+                &image::imageops::resize(&image, stretched_width, image.height(), FilterType::Triangle),
+                size.0,
+                size.1,
+                FilterType::Triangle,
+            );
+            let mut actual = RgbaImage::new(size.0, size.1);
+
+            for (band_y, band) in super::bands(&image, stretched_width, size, band_height) {
+                assert!(band.height() <= band_height, "a band is {} rows tall", band.height());
+
+                image::imageops::replace(&mut actual, &band, 0, band_y as i64);
+            }
+
+            assert_eq!(actual, expected, "the bands differ at {size:?}");
+        }
+    }
+}
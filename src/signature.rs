@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Identification of files that cannot be decoded as images.
+//!
+//! When a file is not in a supported image format, its leading bytes are compared against the signatures of common
+//! documents, archives, and unsupported image formats, so that the reported error can say what the file appears to be.
+
+/// The number of leading bytes that are needed to identify a file.
+pub const LENGTH: usize = 64;
+
+/// Returns a description of the kind of file starting with the given bytes, or [`None`] if it is not recognized.
+pub fn describe(header: &[u8]) -> Option<&'static str> {
+    // The ISO base media format stores its brand after the size of its first box.
+    if let Some(brand) =
+        header.get(4 .. 12).filter(|box_type| box_type.starts_with(b"ftyp")).map(|box_type| &box_type[4 ..])
+    {
+        return Some(match brand {
+            b"heic" | b"heix" | b"mif1" | b"msf1" => "a HEIF image",
+            b"avif" | b"avis" => "an AVIF image",
+            b"qt  " => "a QuickTime video",
+            _ => "an MP4 video",
+        });
+    }
+
+    let text = String::from_utf8_lossy(header);
+    let text = text.trim_start_matches('\u{FEFF}').trim_start();
+
+    Some(match header {
+        [b'%', b'P', b'D', b'F', ..] => "a PDF document",
+        [b'%', b'!', b'P', b'S', ..] => "a PostScript document",
+        [b'P', b'K', 3, 4, ..] => "a ZIP archive",
+        [0x1F, 0x8B, ..] => "a gzip archive",
+        [b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C, ..] => "a 7-Zip archive",
+        [b'R', b'a', b'r', b'!', ..] => "a RAR archive",
+        [0x7F, b'E', b'L', b'F', ..] => "an executable",
+        [0xFF, 0x0A, ..] | [0, 0, 0, 0x0C, b'J', b'X', b'L', b' ', ..] => "a JPEG XL image",
+        [b'8', b'B', b'P', b'S', ..] => "a Photoshop document",
+        [b'I', b'D', b'3', ..] | [0xFF, 0xFB, ..] => "an MP3 file",
+        [0x1A, 0x45, 0xDF, 0xA3, ..] => "a Matroska or WebM video",
+        _ if text.starts_with("<svg") || (text.starts_with("<?xml") && text.contains("<svg")) => "an SVG image",
+        _ if text.get(.. 9).is_some_and(|start| start.eq_ignore_ascii_case("<!doctype"))
+            || text.starts_with("<html") =>
+        {
+            "an HTML document"
+        }
+        _ => return None,
+    })
+}
@@ -0,0 +1,290 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! The images that are drawn, loaded from files, directories and standard input.
+
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::Result;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageError, ImageFormat, ImageReader, Rgba};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use crate::cli::RenderArguments;
+#[cfg(feature = "clipboard")]
+use crate::clipboard;
+use crate::error::Error;
+#[cfg(feature = "raw")]
+use crate::raw;
+use crate::{diff, error, ico, page, pattern, signature};
+
+/// An image that is drawn, along with whether it is fully transparent, which is found once when the image is loaded
+/// rather than each time that it is drawn.
+pub struct SourceImage {
+    pub image: DynamicImage,
+    pub transparent: bool,
+}
+
+impl SourceImage {
+    /// Wraps the given image, treating pixels at or below the alpha threshold as transparent.
+    pub fn new(image: DynamicImage, alpha_threshold: u8) -> Self {
+        let transparent =
+            image.color().has_alpha() && image.pixels().all(|(.., Rgba([.., alpha]))| alpha <= alpha_threshold);
+
+        Self { image, transparent }
+    }
+
+    /// Wraps each of the given frames of an animation.
+    pub fn frames(frames: Box<[DynamicImage]>, alpha_threshold: u8) -> Box<[Self]> {
+        frames.into_iter().map(|frame| Self::new(frame, alpha_threshold)).collect()
+    }
+}
+
+pub fn load_frames(path: &Path, target_size: (u32, u32), page: usize) -> Result<Box<[DynamicImage]>> {
+    if !path.is_dir() {
+        return Ok(Box::new([self::open_image(path, target_size, page)?]));
+    }
+
+    let mut paths = Vec::new();
+
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+
+        if entry.file_type()?.is_file() {
+            paths.push(entry.path());
+        }
+    }
+
+    anyhow::ensure!(!paths.is_empty(), "directory '{}' does not contain any frames", path.display());
+
+    paths.sort_unstable_by(|lhs, rhs| {
+        self::natural_cmp(
+            &lhs.file_name().unwrap_or_default().to_string_lossy(),
+            &rhs.file_name().unwrap_or_default().to_string_lossy(),
+        )
+    });
+
+    paths
+        .par_iter()
+        .map(|path| self::open_image(path, target_size, page))
+        .collect::<error::Result<_>>()
+        .map_err(Into::into)
+}
+
+pub fn open_source(
+    path: Option<&Path>,
+    test_pattern: bool,
+    clipboard: bool,
+    target_size: (u32, u32),
+    page: usize,
+) -> error::Result<DynamicImage> {
+    if test_pattern {
+        return Ok(pattern::generate());
+    }
+    if clipboard {
+        #[cfg(feature = "clipboard")]
+        return clipboard::open();
+        #[cfg(not(feature = "clipboard"))]
+        return Err(Error::decode("reading the clipboard requires the 'clipboard' feature to be enabled"));
+    }
+
+    self::open_image(path.expect("a path is required without a test pattern or the clipboard"), target_size, page)
+}
+
+pub fn trim(arguments: &RenderArguments, frames: &mut [DynamicImage]) -> Result<()> {
+    if !arguments.trim || frames.iter().all(|frame| !frame.color().has_alpha()) {
+        return Ok(());
+    }
+
+    // Every frame of an animation is cropped to the same bounds, so that its contents do not shift between frames.
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+
+    for frame in frames.iter() {
+        let visible = frame.pixels().filter(|(.., Rgba([.., alpha]))| *alpha > arguments.antialias_threshold);
+
+        for (x, y, _) in visible {
+            bounds = Some(match bounds {
+                Some((left, top, right, bottom)) => (left.min(x), top.min(y), right.max(x), bottom.max(y)),
+                None => (x, y, x, y),
+            });
+        }
+    }
+
+    let (left, top, right, bottom) =
+        bounds.ok_or_else(|| anyhow::anyhow!("the image is fully transparent, so it cannot be trimmed"))?;
+
+    for frame in frames {
+        *frame = frame.crop_imm(left, top, right - left + 1, bottom - top + 1);
+    }
+
+    Ok(())
+}
+
+pub fn open_difference(arguments: &RenderArguments, target_size: (u32, u32)) -> Result<Option<DynamicImage>> {
+    arguments.diff.as_deref().map(|path| self::open_image(path, target_size, 1)).transpose().map_err(Into::into)
+}
+
+pub fn difference(
+    arguments: &RenderArguments,
+    source_image: &DynamicImage,
+    other_image: &DynamicImage,
+) -> Result<DynamicImage> {
+    let (width, height) = source_image.dimensions();
+
+    if other_image.dimensions() == (width, height) {
+        return Ok(diff::difference(source_image, other_image, arguments.diff_heat_map));
+    }
+
+    anyhow::ensure!(
+        arguments.diff_resize,
+        "cannot compare a {width}x{height} image with a {}x{} image without `--diff-resize`",
+        other_image.width(),
+        other_image.height()
+    );
+
+    let other_image = other_image.resize_exact(width, height, FilterType::Triangle);
+
+    Ok(diff::difference(source_image, &other_image, arguments.diff_heat_map))
+}
+
+pub fn open_image(path: &Path, target_size: (u32, u32), page: usize) -> error::Result<DynamicImage> {
+    #[cfg(feature = "raw")]
+    if let Some(extension) = path.extension().and_then(|extension| extension.to_str())
+        && raw::EXTENSIONS.iter().any(|raw_extension| extension.eq_ignore_ascii_case(raw_extension))
+    {
+        if page != 1 {
+            return Err(Error::decode(format!("page {page} is out of range, as the image only contains 1 page")));
+        }
+
+        return raw::open(&std::fs::read(path)?);
+    }
+
+    let reader = ImageReader::open(path)?.with_guessed_format()?;
+
+    if reader.format().is_none() {
+        return Err(self::unsupported_format(path, self::file_kind(path)));
+    }
+    if let Some(format) = reader.format()
+        && page > 1
+    {
+        return page::open(&std::fs::read(path)?, format, page);
+    }
+    if reader.format() == Some(ImageFormat::Ico) {
+        return ico::open_closest(&std::fs::read(path)?, target_size);
+    }
+
+    reader.decode().map_err(|error| match error {
+        ImageError::Unsupported(_) => self::unsupported_format(path, self::file_kind(path)),
+        // A file with a misleading extension is decoded as that format, which fails with a less helpful error.
+        ImageError::Decoding(error) => match self::file_kind(path) {
+            Some(kind) => self::unsupported_format(path, Some(kind)),
+            None => ImageError::Decoding(error).into(),
+        },
+        error => error.into(),
+    })
+}
+
+fn file_kind(path: &Path) -> Option<&'static str> {
+    let mut header = Vec::with_capacity(signature::LENGTH);
+
+    File::open(path).and_then(|file| file.take(signature::LENGTH as u64).read_to_end(&mut header)).ok()?;
+
+    signature::describe(&header)
+}
+
+fn unsupported_format(path: &Path, kind: Option<&str>) -> Error {
+    let mut extensions: Vec<&str> = ImageFormat::all()
+        .filter(ImageFormat::reading_enabled)
+        .filter_map(|format| format.extensions_str().first().copied())
+        .collect();
+
+    #[cfg(feature = "raw")]
+    extensions.extend(raw::EXTENSIONS);
+    extensions.sort_unstable();
+
+    let supported = extensions.join(", ");
+
+    Error::decode(match kind {
+        Some(kind) => {
+            format!("'{}' looks like {kind}, which is not supported; supported formats are {supported}", path.display())
+        }
+        None => format!("'{}' is not in a supported image format; supported formats are {supported}", path.display()),
+    })
+}
+
+fn natural_cmp(lhs: &str, rhs: &str) -> Ordering {
+    let (mut lhs, mut rhs) = (lhs, rhs);
+
+    loop {
+        let (Some(lhs_char), Some(rhs_char)) = (lhs.chars().next(), rhs.chars().next()) else {
+            return lhs.len().cmp(&rhs.len());
+        };
+
+        // Runs of digits are compared by their numeric value, so that `frame_2` is ordered before `frame_10`.
+        if lhs_char.is_ascii_digit() && rhs_char.is_ascii_digit() {
+            let lhs_end = lhs.find(|c: char| !c.is_ascii_digit()).unwrap_or(lhs.len());
+            let rhs_end = rhs.find(|c: char| !c.is_ascii_digit()).unwrap_or(rhs.len());
+            let lhs_digits = lhs[.. lhs_end].trim_start_matches('0');
+            let rhs_digits = rhs[.. rhs_end].trim_start_matches('0');
+
+            match lhs_digits.len().cmp(&rhs_digits.len()).then_with(|| lhs_digits.cmp(rhs_digits)) {
+                Ordering::Equal => {}
+                ordering => return ordering,
+            }
+
+            (lhs, rhs) = (&lhs[lhs_end ..], &rhs[rhs_end ..]);
+        } else {
+            match lhs_char.cmp(&rhs_char) {
+                Ordering::Equal => {}
+                ordering => return ordering,
+            }
+
+            (lhs, rhs) = (&lhs[lhs_char.len_utf8() ..], &rhs[rhs_char.len_utf8() ..]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    #[test]
+    fn numbers_are_compared_by_value() {
+        assert_eq!(super::natural_cmp("img2", "img10"), Ordering::Less);
+        assert_eq!(super::natural_cmp("img10", "img9"), Ordering::Greater);
+        assert_eq!(super::natural_cmp("frame_2_b", "frame_2_a"), Ordering::Greater);
+    }
+
+    #[test]
+    fn leading_zeros_are_ignored() {
+        assert_eq!(super::natural_cmp("img007", "img8"), Ordering::Less);
+        assert_eq!(super::natural_cmp("img010", "img9"), Ordering::Greater);
+        assert_eq!(super::natural_cmp("img007", "img7"), Ordering::Equal);
+    }
+
+    #[test]
+    fn letters_are_compared_by_case() {
+        // Uppercase letters are ordered before lowercase letters, as they are by their code points.
+        assert_eq!(super::natural_cmp("Img2", "img1"), Ordering::Less);
+        assert_eq!(super::natural_cmp("img2", "IMG10"), Ordering::Greater);
+    }
+
+    #[test]
+    fn prefixes_are_ordered_first() {
+        assert_eq!(super::natural_cmp("img", "img1"), Ordering::Less);
+        assert_eq!(super::natural_cmp("img1", "img1_alt"), Ordering::Less);
+        assert_eq!(super::natural_cmp("img1", "img1"), Ordering::Equal);
+    }
+}
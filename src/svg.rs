@@ -0,0 +1,131 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Writing of drawn frames as SVG images.
+//!
+//! Each character is placed as its own text element, so the image is not bound to the grid of a terminal. Characters
+//! are normally placed in a grid of cells, but may instead be packed by the advance widths of a proportional font,
+//! where each character is chosen from the cell beneath its center and followed immediately by the next character.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use crossterm::style::Color;
+
+use crate::buffer::FrameBuffer;
+use crate::color::{self, ColorTarget};
+
+/// The size of the font used to draw each character, in pixels.
+const FONT_SIZE: f32 = 16.0;
+/// The width of a cell relative to the font size when characters are placed in a grid.
+const GRID_ADVANCE: f32 = 0.6;
+
+/// Writes the given frame as an SVG image drawn in the given font over the given background.
+///
+/// If advance widths are given, relative to the font size, characters are packed by their advances, and each cell is
+/// as wide as the average advance.
+pub fn write(
+    writer: &mut impl Write,
+    frame: &FrameBuffer,
+    font_family: Option<&str>,
+    background: [u8; 3],
+    advances: Option<&HashMap<char, f32>>,
+) -> std::io::Result<()> {
+    let advances = advances.filter(|advances| !advances.is_empty());
+    let cell_advance = advances.map_or(GRID_ADVANCE, |advances| advances.values().sum::<f32>() / advances.len() as f32);
+    // Cells are twice as tall as they are wide, matching the proportions assumed when the frame was drawn.
+    let cell_width = FONT_SIZE * cell_advance;
+    let cell_height = cell_width * 2.0;
+    let (width, height) = frame.size();
+    let (image_width, image_height) = (width as f32 * cell_width, height as f32 * cell_height);
+    let foreground =
+        if background.iter().map(|channel| *channel as u32).sum::<u32>() < 0x180 { [0xFF; 3] } else { [0; 3] };
+
+    writeln!(
+        writer,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{image_width}" height="{image_height}" viewBox="0 0 {image_width} {image_height}">"#
+    )?;
+    writeln!(writer, r#"<rect width="100%" height="100%" fill="{}"/>"#, self::hex(background))?;
+    writeln!(
+        writer,
+        r#"<g font-family="{}" font-size="{FONT_SIZE}" fill="{}" xml:space="preserve">"#,
+        self::escape(font_family.unwrap_or("monospace")),
+        self::hex(foreground)
+    )?;
+
+    for y in 0 .. height {
+        let top = y as f32 * cell_height;
+        // The baseline is placed so that the font's ascent and descent are centered within the row.
+        let baseline = top + (cell_height + FONT_SIZE * 0.7) / 2.0;
+        let mut x = 0.0_f32;
+
+        while x < image_width {
+            let column = (x / cell_width) as u16;
+            let Some(cell) = frame.get(column, y) else {
+                x += cell_width;
+
+                continue;
+            };
+            let advance = advances.and_then(|advances| advances.get(&cell.character)).map_or(cell_width, |advance| {
+                // Glyphs without an advance would never move past their cell.
+                (advance * FONT_SIZE).max(cell_width / 8.0)
+            });
+            // Proportional characters are chosen from the cell beneath their center rather than their left edge.
+            let cell = match advances {
+                Some(_) => frame.get(((x + advance / 2.0) / cell_width) as u16, y).unwrap_or(cell),
+                None => cell,
+            };
+            let fill = cell.color.and_then(self::rgb);
+
+            if let (Some(fill), ColorTarget::Background) = (fill, cell.target) {
+                writeln!(
+                    writer,
+                    r#"<rect x="{x}" y="{top}" width="{advance}" height="{cell_height}" fill="{}"/>"#,
+                    self::hex(fill)
+                )?;
+            }
+            if cell.character != ' ' {
+                let character = self::escape(&cell.character.to_string());
+
+                match (fill, cell.target) {
+                    (Some(fill), ColorTarget::Foreground) => writeln!(
+                        writer,
+                        r#"<text x="{x}" y="{baseline}" fill="{}">{character}</text>"#,
+                        self::hex(fill)
+                    )?,
+                    _ => writeln!(writer, r#"<text x="{x}" y="{baseline}">{character}</text>"#)?,
+                }
+            }
+
+            x += advance;
+        }
+    }
+
+    writeln!(writer, "</g>")?;
+    writeln!(writer, "</svg>")
+}
+
+fn rgb(color: Color) -> Option<[u8; 3]> {
+    match color {
+        Color::Rgb { r, g, b } => Some([r, g, b]),
+        Color::AnsiValue(index) => Some(color::ansi_rgb(index)),
+        _ => None,
+    }
+}
+
+fn hex([red, green, blue]: [u8; 3]) -> String {
+    format!("#{red:02X}{green:02X}{blue:02X}")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,124 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public
+// License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied
+// warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Queries of the terminal's state.
+//!
+//! Terminals answer queries by writing escape sequences to their input, so the answers must be read before any other
+//! input is handled. Not every terminal answers every query, so each query is followed by a request for the
+//! terminal's primary device attributes, which every terminal answers, to avoid waiting for answers that never come.
+//!
+//! Queries are written to the controlling terminal rather than to standard output, which may be redirected to a file
+//! or piped to another program while the image is drawn to standard error.
+
+use std::fs::{File, OpenOptions};
+use std::io::{IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+const QUERY_TIMEOUT: Duration = Duration::from_millis(200);
+const MAX_RESPONSE_LENGTH: usize = 256;
+
+/// Returns the terminal's background color, or [`None`] if it could not be determined.
+pub fn background_color() -> Option<[u8; 3]> {
+    let response = self::query(b"\x1B]11;?\x1B\\")?;
+    let response = String::from_utf8_lossy(&response);
+    // The color is reported as `rgb:RRRR/GGGG/BBBB`, where each channel has between one and four hex digits.
+    let (_, color) = response.split_once("rgb:")?;
+    let mut channels = color.split('/').map(|channel| {
+        let digits: String = channel.chars().take_while(char::is_ascii_hexdigit).take(4).collect();
+        let maximum = 16_u32.checked_pow(digits.len() as u32)? - 1;
+
+        u32::from_str_radix(&digits, 16).ok().map(|value| (value * u8::MAX as u32 / maximum) as u8)
+    });
+
+    Some([channels.next()??, channels.next()??, channels.next()??])
+}
+
+/// Returns the parameters of the terminal's primary device attributes, or [`None`] if it did not report them.
+///
+/// The first parameter identifies the conformance level of the terminal, and the rest list the features it supports,
+/// such as `4` for SIXEL graphics.
+pub fn device_attributes() -> Option<Box<[u16]>> {
+    let response = self::query(b"")?;
+    let response = String::from_utf8_lossy(&response);
+    let (_, attributes) = response.rsplit_once("\x1B[?")?;
+
+    Some(attributes.trim_end_matches('c').split(';').filter_map(|attribute| attribute.parse().ok()).collect())
+}
+
+/// Returns whether the terminal supports the Kitty graphics protocol, or [`None`] if it could not be determined.
+pub fn supports_kitty_graphics() -> Option<bool> {
+    // A single transparent pixel is queried, which terminals that support the protocol validate without displaying.
+    let response = self::query(b"\x1B_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1B\\")?;
+
+    Some(response.windows(10).any(|window| window == b"_Gi=31;OK\x1B"))
+}
+
+fn query(request: &[u8]) -> Option<Vec<u8>> {
+    let tty = OpenOptions::new().write(true).open("/dev/tty").ok()?;
+
+    if !std::io::stdin().is_terminal() || !tty.is_terminal() {
+        return None;
+    }
+
+    let was_raw = crossterm::terminal::is_raw_mode_enabled().ok()?;
+
+    if !was_raw {
+        crossterm::terminal::enable_raw_mode().ok()?;
+    }
+
+    let response = self::read_response(tty, request);
+
+    if !was_raw {
+        crossterm::terminal::disable_raw_mode().ok()?;
+    }
+
+    response
+}
+
+fn read_response(mut tty: File, request: &[u8]) -> Option<Vec<u8>> {
+    tty.write_all(request).ok()?;
+    tty.write_all(b"\x1B[c").ok()?;
+    tty.flush().ok()?;
+
+    let deadline = Instant::now() + QUERY_TIMEOUT;
+    let mut response = Vec::new();
+
+    // The device attributes are reported as `ESC [ ? ... c`, and always arrive after the answer to the query.
+    while !response
+        .rsplit(|byte| *byte == 0x1B)
+        .next()
+        .is_some_and(|last| last.starts_with(b"[?") && last.ends_with(b"c"))
+    {
+        let timeout = deadline.saturating_duration_since(Instant::now()).as_millis() as libc::c_int;
+        let mut poll_fd = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+
+        // SAFETY: The file descriptor array contains exactly one valid entry.
+        if timeout == 0 || unsafe { libc::poll(&mut poll_fd, 1, timeout) } <= 0 {
+            return None;
+        }
+
+        let mut byte = 0_u8;
+
+        // SAFETY: The buffer is a single byte, which is the length that is read.
+        if unsafe { libc::read(libc::STDIN_FILENO, (&raw mut byte).cast(), 1) } != 1 {
+            return None;
+        }
+
+        response.push(byte);
+
+        if response.len() > MAX_RESPONSE_LENGTH {
+            return None;
+        }
+    }
+
+    Some(response)
+}